@@ -0,0 +1,206 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, Expr, FnArg, ItemFn, Pat, ReturnType, Token, Type};
+
+/// Arguments of the attribute: `cache = <expr>` and optionally `tags = [<expr>, ...]`
+struct CachedArgs {
+    cache: Expr,
+    tags: Vec<Expr>,
+}
+
+impl Parse for CachedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut cache = None;
+        let mut tags = vec![];
+        while !input.is_empty() {
+            let name: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match name.to_string().as_str() {
+                "cache" => cache = Some(input.parse()?),
+                "tags" => {
+                    let list;
+                    syn::bracketed!(list in input);
+                    tags = Punctuated::<Expr, Token![,]>::parse_terminated(&list)?
+                        .into_iter()
+                        .collect();
+                }
+                _ => return Err(Error::new(name.span(), "expected `cache` or `tags`")),
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let cache = cache.ok_or_else(|| Error::new(Span::call_site(), "missing `cache = ...`"))?;
+        Ok(Self { cache, tags })
+    }
+}
+
+/// Memoizes a function in a DashmapCache
+///
+/// `#[dashmap_cached(cache = MY_CACHE, tags = ["user"])]`
+///
+/// The key is derived from all the arguments, which must be Clone + Hash + Eq + Serialize,
+/// under a namespace named after the function so that memoized functions sharing a cache don't collide.
+/// The return type must be Clone + Serialize + Deserialize.
+/// `async fn` goes through async_cached(), anything else through cached().
+/// The body runs at most once per call whatever the cache does: if the cache fails after it ran,
+/// its value is returned uncached, and if it fails before, such as on an argument that doesn't
+/// encode, the body runs uncached. A computation the cache stopped, timed out or panicking under
+/// PanicPolicy::Error, has no value to return and panics with the CacheError, as does a panic
+/// replayed under PanicPolicy::CacheFor
+#[proc_macro_attribute]
+pub fn dashmap_cached(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as CachedArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    expand(args, func)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(args: CachedArgs, func: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+
+    let mut idents = vec![];
+    let mut types: Vec<Type> = vec![];
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new_spanned(
+                    receiver,
+                    "dashmap_cached doesn't support methods",
+                ))
+            }
+            FnArg::Typed(typed) => match &*typed.pat {
+                Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {
+                    idents.push(pat.ident.clone());
+                    types.push((*typed.ty).clone());
+                }
+                pat => {
+                    return Err(Error::new_spanned(
+                        pat,
+                        "dashmap_cached arguments must be plain identifiers",
+                    ))
+                }
+            },
+        }
+    }
+
+    let output: Type = match &sig.output {
+        ReturnType::Default => syn::parse_quote!(()),
+        ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+
+    // The body keeps the original signature, mut bindings included, under another name
+    let mut inner_sig = sig.clone();
+    inner_sig.ident = syn::Ident::new("__dashmap_cached_inner", Span::call_site());
+    let inner = &inner_sig.ident;
+    let mut outer_sig = sig.clone();
+    for input in outer_sig.inputs.iter_mut() {
+        if let FnArg::Typed(typed) = input {
+            if let Pat::Ident(pat) = &mut *typed.pat {
+                pat.mutability = None;
+            }
+        }
+    }
+
+    let name = sig.ident.to_string();
+    let cache = &args.cache;
+    let tags = &args.tags;
+    let clones = idents
+        .iter()
+        .zip(&types)
+        .map(|(ident, ty)| quote!(<#ty as ::core::clone::Clone>::clone(#ident)));
+
+    // What the body did is recorded so that a cache error never gets it to run twice
+    let record = quote! {
+        __started.store(true, ::core::sync::atomic::Ordering::Relaxed);
+    };
+    let keep = quote! {
+        *__computed
+            .lock()
+            .unwrap_or_else(::std::sync::PoisonError::into_inner) =
+            ::core::option::Option::Some(<#output as ::core::clone::Clone>::clone(&__val));
+    };
+    let (cached, uncached) = if sig.asyncness.is_some() {
+        let cached = quote! {
+            __handle
+                .async_cached(
+                    &__tags,
+                    |(#(#idents,)*): (#(&#types,)*)| {
+                        let (__started, __computed) = (&__started, &__computed);
+                        async move {
+                            #record
+                            let __val = #inner(#(#clones),*).await;
+                            #keep
+                            __val
+                        }
+                    },
+                    (#(&#idents,)*),
+                )
+                .await
+        };
+        (cached, quote!(#inner(#(#idents),*).await))
+    } else {
+        let cached = quote! {
+            __handle.cached(
+                &__tags,
+                |__args: &(#(&#types,)*)| {
+                    let (#(#idents,)*) = *__args;
+                    #record
+                    let __val = #inner(#(#clones),*);
+                    #keep
+                    __val
+                },
+                (#(&#idents,)*),
+            )
+        };
+        (cached, quote!(#inner(#(#idents),*)))
+    };
+    let call = quote! {
+        let __started = ::core::sync::atomic::AtomicBool::new(false);
+        let __computed = ::std::sync::Mutex::new(::core::option::Option::<#output>::None);
+        let __cached = #cached;
+        match __cached {
+            ::core::result::Result::Ok(__val) => __val,
+            ::core::result::Result::Err(__err) => {
+                let __computed = __computed
+                    .into_inner()
+                    .unwrap_or_else(::std::sync::PoisonError::into_inner);
+                match (__computed, __err) {
+                    (::core::option::Option::Some(__val), _) => __val,
+                    (::core::option::Option::None, __err @ ::dashmap_cache::CacheError::Panicked(_)) => {
+                        ::core::panic!("{}", __err)
+                    }
+                    (::core::option::Option::None, _)
+                        if !__started.load(::core::sync::atomic::Ordering::Relaxed) =>
+                    {
+                        #uncached
+                    }
+                    (::core::option::Option::None, __err) => ::core::panic!("{}", __err),
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #outer_sig {
+            #inner_sig #block
+
+            let __handle = (#cache).typed::<(#(&#types,)*), #output>(
+                ::core::concat!(::core::module_path!(), "::", #name),
+            );
+            let __tags: ::std::vec::Vec<::std::string::String> =
+                ::std::vec![#(::std::string::ToString::to_string(&#tags)),*];
+            #call
+        }
+    })
+}
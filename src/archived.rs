@@ -0,0 +1,138 @@
+use core::cell::Cell;
+use core::hash::Hash;
+use rkyv::api::high::{HighSerializer, HighValidator};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor;
+use rkyv::ser::allocator::ArenaHandle;
+use rkyv::util::AlignedVec;
+use rkyv::Archive;
+use serde::Serialize;
+
+use crate::compression::RAW;
+use crate::entry::Entry;
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Alignment rkyv::to_bytes() gives its buffers, archived values are read in place when stored at it
+const ALIGNMENT: usize = 16;
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Caches val as an rkyv archive for arg, to be read in place by with_archived()
+    /// Archives bypass the cache serializer: read them only through the *_archived() methods
+    /// Waits for a fill of the key in progress the way put() does
+    pub fn put_archived<A, T>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        arg: &A,
+        val: &T,
+    ) -> Result<(), CacheError>
+    where
+        A: Serialize,
+        T: Archive
+            + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+    {
+        let key = self.key_of(arg)?;
+        let archive =
+            rkyv::to_bytes::<rancor::Error>(val).map_err(|e| CacheError::Codec(Box::new(e)))?;
+        let _flight = self.lock_key_at(key.clone());
+        self.insert(
+            invalidate_keys,
+            &key,
+            Entry::new(self.stored_archive(&archive)?, self.default_expiry()),
+        );
+        Ok(())
+    }
+
+    /// Runs f on the archived value cached for arg by put_archived() or cached_archived()
+    /// The archive is validated but not deserialized, and is only copied when it was compressed or
+    /// is not stored at the alignment rkyv needs, which always happens once compression is enabled
+    /// The entry is locked for reading while f runs: f must not write to the cache
+    pub fn with_archived<A, T, R, F>(&self, arg: &A, f: F) -> Result<Option<R>, CacheError>
+    where
+        A: Serialize,
+        T: Archive,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+        F: FnOnce(&T::Archived) -> R,
+    {
+        let key = self.key_of(arg)?;
+        if let Some(entry) = self.live_entry(&key) {
+            StatCounters::incr(&self.stats.hits, 1);
+            return self.access_archived::<T, R, F>(&entry.value, f).map(Some);
+        }
+        match self.fetch_backend(&key) {
+            Some(stored) => self.access_archived::<T, R, F>(&stored, f).map(Some),
+            None => {
+                StatCounters::incr(&self.stats.misses, 1);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Same as cached() for values stored as rkyv archives, f reading the archive as in with_archived()
+    pub fn cached_archived<C, A, T, R, F>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: C,
+        arg: A,
+        f: F,
+    ) -> Result<R, CacheError>
+    where
+        C: Fn(&A) -> T,
+        A: Hash + Sync + Send + Eq + Serialize,
+        T: Archive
+            + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+        F: FnOnce(&T::Archived) -> R,
+    {
+        // f is called once, either on the hit that ends the lookup or on the computed value
+        let f = Cell::new(Some(f));
+        let take_f = || f.take().expect("f is only called once");
+        let (output, _outcome) = self.single_flight(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |stored| self.access_archived::<T, R, F>(stored, take_f()),
+            || Ok::<_, CacheError>(closure(&arg)),
+            |val| {
+                let archive = rkyv::to_bytes::<rancor::Error>(&val)
+                    .map_err(|e| CacheError::Codec(Box::new(e)))?;
+                let entry = Entry::new(self.stored_archive(&archive)?, self.default_expiry());
+                let archived = rkyv::access::<T::Archived, rancor::Error>(&archive)
+                    .map_err(|e| CacheError::Codec(Box::new(e)))?;
+                Ok((take_f()(archived), Some(entry)))
+            },
+        )?;
+        Ok(output)
+    }
+
+    /// Archive framed and compressed the way encode() does it for serialized values
+    fn stored_archive(&self, archive: &[u8]) -> Result<Vec<u8>, CacheError> {
+        if !self.framed() {
+            return Ok(archive.to_vec());
+        }
+        let mut buf = Vec::with_capacity(archive.len() + 1);
+        buf.push(RAW);
+        buf.extend_from_slice(archive);
+        Ok(self.compressed(&buf)?.unwrap_or(buf))
+    }
+
+    fn access_archived<T, R, F>(&self, stored: &[u8], f: F) -> Result<R, CacheError>
+    where
+        T: Archive,
+        T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+        F: FnOnce(&T::Archived) -> R,
+    {
+        let raw = self.raw_value(stored)?;
+        let aligned;
+        let bytes = if raw.as_ptr().align_offset(ALIGNMENT) == 0 {
+            &raw[..]
+        } else {
+            let mut copy = AlignedVec::<ALIGNMENT>::with_capacity(raw.len());
+            copy.extend_from_slice(&raw);
+            aligned = copy;
+            &aligned[..]
+        };
+        let archived = rkyv::access::<T::Archived, rancor::Error>(bytes)
+            .map_err(|e| CacheError::Codec(Box::new(e)))?;
+        Ok(f(archived))
+    }
+}
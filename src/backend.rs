@@ -0,0 +1,168 @@
+use core::fmt::Debug;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::builder::RefreshTagPolicy;
+use crate::entry::{Entry, Expiry};
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Value stored in a CacheBackend, bytes being encoded by the cache in front of it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackendEntry {
+    pub value: Vec<u8>,
+    pub tags: Vec<String>,
+    /// Time left before the backend drops the value
+    pub ttl: Option<Duration>,
+}
+
+/// Store a DashmapCache writes through to and reads its misses from, see DashmapCacheBuilder::backend()
+/// Keys and values are opaque bytes, tags group keys the same way they do in the cache
+pub trait CacheBackend: Send + Sync + Debug {
+    fn get(&self, key: &[u8]) -> Result<Option<BackendEntry>, CacheError>;
+
+    fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        tags: &[String],
+        ttl: Option<Duration>,
+    ) -> Result<(), CacheError>;
+
+    /// Returns whether there was a value for key
+    fn remove(&self, key: &[u8]) -> Result<bool, CacheError>;
+
+    /// Removes every key tagged with tag
+    fn invalidate_tag(&self, tag: &str) -> Result<(), CacheError>;
+}
+
+impl<B: CacheBackend + ?Sized> CacheBackend for Arc<B> {
+    fn get(&self, key: &[u8]) -> Result<Option<BackendEntry>, CacheError> {
+        (**self).get(key)
+    }
+
+    fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        tags: &[String],
+        ttl: Option<Duration>,
+    ) -> Result<(), CacheError> {
+        (**self).insert(key, value, tags, ttl)
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<bool, CacheError> {
+        (**self).remove(key)
+    }
+
+    fn invalidate_tag(&self, tag: &str) -> Result<(), CacheError> {
+        (**self).invalidate_tag(tag)
+    }
+}
+
+/// A DashmapCache can itself back another one, sharing it between caches with different settings
+impl<S: Serializer + Debug> CacheBackend for DashmapCache<S> {
+    fn get(&self, key: &[u8]) -> Result<Option<BackendEntry>, CacheError> {
+        let Some(entry) = self.live_entry(key) else {
+            StatCounters::incr(&self.stats.misses, 1);
+            return Ok(None);
+        };
+        StatCounters::incr(&self.stats.hits, 1);
+        let now = Instant::now();
+        Ok(Some(BackendEntry {
+            value: entry.value.clone(),
+            tags: entry.tags.clone(),
+            ttl: entry
+                .expires_at
+                .map(|expires_at| expires_at.saturating_duration_since(now)),
+        }))
+    }
+
+    fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        tags: &[String],
+        ttl: Option<Duration>,
+    ) -> Result<(), CacheError> {
+        let entry = Entry::new(value.to_vec(), Expiry::from_ttl(ttl));
+        self.insert_tagged(&tags.to_vec(), key, entry, RefreshTagPolicy::Merge);
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<bool, CacheError> {
+        Ok(self.remove_key(key))
+    }
+
+    fn invalidate_tag(&self, tag: &str) -> Result<(), CacheError> {
+        self.invalidate(tag);
+        Ok(())
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Runs op against the backend if there is one
+    /// Backend failures don't fail cache calls, they are kept for take_last_error() instead
+    pub(crate) fn with_backend<T>(
+        &self,
+        op: impl FnOnce(&dyn CacheBackend) -> Result<T, CacheError>,
+    ) -> Option<T> {
+        let backend = self.backend.as_deref()?;
+        op(backend).map_err(|err| self.record_error(err)).ok()
+    }
+
+    /// Fetches a local miss from the backend, keeping a local copy of what it finds
+    pub(crate) fn lookup_backend<V: for<'b> Deserialize<'b>>(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<V>, CacheError> {
+        let Some(stored) = self.fetch_backend(key) else {
+            return Ok(None);
+        };
+        Ok(Some(self.decode(&stored)?))
+    }
+
+    /// lookup_backend() returning the stored bytes
+    pub(crate) fn fetch_backend(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let remote = self.with_backend(|backend| backend.get(key)).flatten()?;
+        StatCounters::incr(&self.stats.hits, 1);
+        let entry = Entry::new(remote.value.clone(), Expiry::from_ttl(remote.ttl));
+        self.insert_local(&remote.tags, key, entry, RefreshTagPolicy::Merge);
+        Some(remote.value)
+    }
+
+    /// Sends the entry just stored under key to the backend
+    pub(crate) fn write_through(&self, key: &[u8]) {
+        if self.backend.is_none() {
+            return;
+        }
+        let Some((value, tags, expires_at)) = self
+            .inner
+            .get(key)
+            .map(|entry| (entry.value.clone(), entry.tags.clone(), entry.expires_at))
+        else {
+            return;
+        };
+        let ttl = expires_at.map(|expires_at| expires_at.saturating_duration_since(Instant::now()));
+        self.with_backend(|backend| backend.insert(key, &value, &tags, ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misses_ask_the_backend_once() {
+        let shared = Arc::new(DashmapCache::new());
+        let front = DashmapCache::builder().backend(shared.clone()).build();
+        assert_eq!(front.cached(&vec![], |x| x * 2, 3u64).unwrap(), 6);
+        assert_eq!(shared.stats().misses, 1);
+        let other = DashmapCache::builder().backend(shared.clone()).build();
+        assert_eq!(other.cached(&vec![], |_| 0, 3u64).unwrap(), 6);
+        assert_eq!(shared.stats().hits, 1);
+        assert_eq!(other.cached(&vec![], |_| 0, 3u64).unwrap(), 6);
+        assert_eq!(shared.stats().hits, 1);
+    }
+}
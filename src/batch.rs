@@ -0,0 +1,180 @@
+use core::future::Future;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::marker::{Send, Sync};
+
+use crate::lock::KeyGuard;
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Args of a batch split between the values found in the cache and the keys left to compute
+struct Batch<A, V> {
+    keys: Vec<Vec<u8>>,
+    found: HashMap<Vec<u8>, V>,
+    missing: Vec<(Vec<u8>, A)>,
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// cached() over a batch of args, the misses being computed by a single closure call
+    /// The closure gets each missing arg once, even if it appears several times in args, and returns their values
+    /// in the same order, so that a `WHERE id IN (...)` query replaces one query per arg
+    /// Values are returned in the order of args
+    pub fn cached_many<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        args: Vec<A>,
+    ) -> Result<Vec<V>, CacheError>
+    where
+        F: FnOnce(&[A]) -> Vec<V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let mut batch = self.split_batch(args)?;
+        if batch.missing.is_empty() {
+            return Ok(batch.collect());
+        }
+        let _flights: Vec<KeyGuard<'_, S>> = batch
+            .lock_order()
+            .map(|key| self.lock_key_at(key))
+            .collect();
+        self.recheck_batch(&mut batch)?;
+        let epochs = self.epochs_of(invalidate_keys);
+        let (keys, missing): (Vec<Vec<u8>>, Vec<A>) =
+            std::mem::take(&mut batch.missing).into_iter().unzip();
+        let vals = if missing.is_empty() {
+            vec![]
+        } else {
+            self.compute(&keys, invalidate_keys, || {
+                self.guard_panic(&keys, || closure(&missing))
+            })?
+        };
+        self.fill_batch(invalidate_keys, &mut batch, keys, vals, &epochs)?;
+        Ok(batch.collect())
+    }
+
+    /// Async version of cached_many(), the closure takes the missing args by value
+    pub async fn async_cached_many<F, Fut, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        args: Vec<A>,
+    ) -> Result<Vec<V>, CacheError>
+    where
+        F: FnOnce(Vec<A>) -> Fut,
+        Fut: Future<Output = Vec<V>>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let mut batch = self.split_batch(args)?;
+        if batch.missing.is_empty() {
+            return Ok(batch.collect());
+        }
+        let mut flights: Vec<KeyGuard<'_, S>> = Vec::with_capacity(batch.missing.len());
+        for key in batch.lock_order() {
+            flights.push(self.lock_key_at_async(key).await);
+        }
+        self.recheck_batch(&mut batch)?;
+        let epochs = self.epochs_of(invalidate_keys);
+        let (keys, missing): (Vec<Vec<u8>>, Vec<A>) =
+            std::mem::take(&mut batch.missing).into_iter().unzip();
+        let vals = if missing.is_empty() {
+            vec![]
+        } else {
+            let fut = self.guard_panic(&keys, || closure(missing))?;
+            let fut = self.timed(async { Ok::<_, CacheError>(fut.await) });
+            let fut = self.guard_panic_async(&keys, fut);
+            self.compute_async(&keys, invalidate_keys, fut).await??
+        };
+        self.fill_batch(invalidate_keys, &mut batch, keys, vals, &epochs)?;
+        Ok(batch.collect())
+    }
+
+    fn split_batch<A: Serialize, V: for<'b> Deserialize<'b>>(
+        &self,
+        args: Vec<A>,
+    ) -> Result<Batch<A, V>, CacheError> {
+        let mut batch = Batch {
+            keys: Vec::with_capacity(args.len()),
+            found: HashMap::new(),
+            missing: vec![],
+        };
+        let mut seen = HashSet::new();
+        for arg in args {
+            let key = self.key_of(&arg)?;
+            batch.keys.push(key.clone());
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            match self.lookup(&key)? {
+                Some(val) => {
+                    batch.found.insert(key, val);
+                }
+                None => batch.missing.push((key, arg)),
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Looks the missing keys up again once locked, another call may have filled them meanwhile
+    fn recheck_batch<A, V: for<'b> Deserialize<'b>>(
+        &self,
+        batch: &mut Batch<A, V>,
+    ) -> Result<(), CacheError> {
+        let mut missing = Vec::with_capacity(batch.missing.len());
+        for (key, arg) in std::mem::take(&mut batch.missing) {
+            match self.lookup(&key)? {
+                Some(val) => {
+                    batch.found.insert(key, val);
+                }
+                None => missing.push((key, arg)),
+            }
+        }
+        batch.missing = missing;
+        let keys: Vec<&[u8]> = batch.missing.iter().map(|(key, _arg)| &key[..]).collect();
+        if let Some(err) = self.cached_panic_of(&keys) {
+            return Err(err);
+        }
+        StatCounters::incr(&self.stats.misses, batch.missing.len() as u64);
+        Ok(())
+    }
+
+    fn fill_batch<A, V: Serialize>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        batch: &mut Batch<A, V>,
+        keys: Vec<Vec<u8>>,
+        vals: Vec<V>,
+        epochs: &[u64],
+    ) -> Result<(), CacheError> {
+        if vals.len() != keys.len() {
+            return Err(CacheError::BatchLength {
+                expected: keys.len(),
+                returned: vals.len(),
+            });
+        }
+        let expiry = self.default_expiry();
+        for (key, val) in keys.into_iter().zip(vals) {
+            self.fill(invalidate_keys, &key, &val, expiry, epochs)?;
+            batch.found.insert(key, val);
+        }
+        Ok(())
+    }
+}
+
+impl<A, V: Clone> Batch<A, V> {
+    /// Keys of the misses sorted, so that concurrent batches lock their common keys in the same order
+    fn lock_order(&self) -> impl Iterator<Item = Vec<u8>> {
+        let mut keys: Vec<Vec<u8>> = self.missing.iter().map(|(key, _arg)| key.clone()).collect();
+        keys.sort_unstable();
+        keys.into_iter()
+    }
+
+    fn collect(self) -> Vec<V> {
+        self.keys
+            .iter()
+            .map(|key| self.found[key].clone())
+            .collect()
+    }
+}
@@ -0,0 +1,445 @@
+use core::hash::Hash;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    CacheBackend, Compression, DashmapCache, EvictionPolicy, InvalidationTransport, KeyStrategy,
+    MsgPack, PanicPolicy, Serializer, TagPolicy,
+};
+
+/// What `refresh_cache` does with the tags of a key that is already cached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefreshTagPolicy {
+    /// The key ends up tagged exactly with the refresh tag list, previous tags are dropped
+    Replace,
+    /// The key keeps its previous tags, the refresh tag list only applies to new keys
+    Preserve,
+    /// The refresh tag list is added on top of the previous tags
+    #[default]
+    Merge,
+}
+
+/// Configures a DashmapCache before it is created
+#[derive(Clone, Debug)]
+pub struct DashmapCacheBuilder<S = MsgPack> {
+    pub(crate) refresh_tag_policy: RefreshTagPolicy,
+    pub(crate) shard_amount: Option<usize>,
+    pub(crate) initial_capacity: usize,
+    pub(crate) initial_tag_capacity: usize,
+    pub(crate) return_value_on_cache_error: bool,
+    pub(crate) sweep_interval: Duration,
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) max_bytes: Option<usize>,
+    pub(crate) max_weight: Option<u64>,
+    pub(crate) default_ttl: Option<Duration>,
+    pub(crate) ttl_jitter: u8,
+    pub(crate) panic_policy: PanicPolicy,
+    #[cfg(feature = "tokio")]
+    pub(crate) timeout: Option<Duration>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics_name: Arc<str>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics_tag_labels: bool,
+    pub(crate) key_strategy: KeyStrategy,
+    pub(crate) compression: Compression,
+    pub(crate) compression_threshold: usize,
+    pub(crate) backend: Option<Arc<dyn CacheBackend>>,
+    pub(crate) transport: Option<Arc<dyn InvalidationTransport>>,
+    pub(crate) tag_policies: HashMap<String, TagPolicy>,
+    pub(crate) eviction_policy: EvictionPolicy,
+    pub(crate) serializer: S,
+}
+
+impl Default for DashmapCacheBuilder {
+    fn default() -> Self {
+        Self {
+            refresh_tag_policy: RefreshTagPolicy::default(),
+            shard_amount: None,
+            initial_capacity: 0,
+            initial_tag_capacity: 0,
+            return_value_on_cache_error: false,
+            sweep_interval: Duration::from_secs(60),
+            max_entries: None,
+            max_bytes: None,
+            max_weight: None,
+            default_ttl: None,
+            ttl_jitter: 0,
+            panic_policy: PanicPolicy::default(),
+            #[cfg(feature = "tokio")]
+            timeout: None,
+            #[cfg(feature = "metrics")]
+            metrics_name: Arc::from("default"),
+            #[cfg(feature = "metrics")]
+            metrics_tag_labels: false,
+            key_strategy: KeyStrategy::default(),
+            compression: Compression::default(),
+            compression_threshold: 1024,
+            backend: None,
+            transport: None,
+            tag_policies: HashMap::new(),
+            eviction_policy: EvictionPolicy::default(),
+            serializer: MsgPack,
+        }
+    }
+}
+
+/// Reasons a cache configuration can't be turned into a cache
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// DashMap needs a power of two greater than 1
+    InvalidShardAmount(usize),
+    /// A cache bounded to 0 entries could not even keep the value it just computed
+    ZeroMaxEntries,
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidShardAmount(amount) => write!(
+                f,
+                "shard amount {amount} is not a power of two greater than 1"
+            ),
+            Self::ZeroMaxEntries => f.write_str("max_entries must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl DashmapCacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Serializer> DashmapCacheBuilder<S> {
+    /// Codec used for cached values, MsgPack by default
+    pub fn serializer<T: Serializer>(self, serializer: T) -> DashmapCacheBuilder<T> {
+        DashmapCacheBuilder {
+            refresh_tag_policy: self.refresh_tag_policy,
+            shard_amount: self.shard_amount,
+            initial_capacity: self.initial_capacity,
+            initial_tag_capacity: self.initial_tag_capacity,
+            return_value_on_cache_error: self.return_value_on_cache_error,
+            sweep_interval: self.sweep_interval,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            max_weight: self.max_weight,
+            default_ttl: self.default_ttl,
+            ttl_jitter: self.ttl_jitter,
+            panic_policy: self.panic_policy,
+            #[cfg(feature = "tokio")]
+            timeout: self.timeout,
+            #[cfg(feature = "metrics")]
+            metrics_name: self.metrics_name,
+            #[cfg(feature = "metrics")]
+            metrics_tag_labels: self.metrics_tag_labels,
+            key_strategy: self.key_strategy,
+            compression: self.compression,
+            compression_threshold: self.compression_threshold,
+            backend: self.backend,
+            transport: self.transport,
+            tag_policies: self.tag_policies,
+            eviction_policy: self.eviction_policy,
+            serializer,
+        }
+    }
+
+    /// Controls how refresh_cache treats the tags of an existing key
+    pub fn refresh_tag_policy(mut self, policy: RefreshTagPolicy) -> Self {
+        self.refresh_tag_policy = policy;
+        self
+    }
+
+    /// Number of shards of the underlying DashMaps, must be a power of two greater than 1
+    /// Defaults to DashMap's own choice based on available parallelism
+    #[doc(alias = "shards")]
+    pub fn shard_amount(mut self, shard_amount: usize) -> Self {
+        self.shard_amount = Some(shard_amount);
+        self
+    }
+
+    /// Number of entries the cache can hold before reallocating
+    pub fn initial_capacity(mut self, capacity: usize) -> Self {
+        self.initial_capacity = capacity;
+        self
+    }
+
+    /// Number of distinct tags the cache can hold before reallocating its tag index
+    pub fn initial_tag_capacity(mut self, capacity: usize) -> Self {
+        self.initial_tag_capacity = capacity;
+        self
+    }
+
+    /// When a computed value can't be serialized, the cached() family still returns it and only skips caching
+    /// The encoding error is then available through DashmapCache::take_last_error()
+    pub fn return_value_on_cache_error(mut self, enabled: bool) -> Self {
+        self.return_value_on_cache_error = enabled;
+        self
+    }
+
+    /// Bounds the cache to max_entries, evicting according to the eviction policy beyond that
+    /// 0 is rejected, see validate()
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Bounds the serialized size of the cached keys and values, see DashmapCache::approx_bytes()
+    /// Can be combined with max_entries, entries are evicted as soon as either is exceeded
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Bounds the sum of the entry weights, entries not cached with cached_weighted() weighing 1
+    /// Pair it with EvictionPolicy::Weighted to prefer keeping the heavier entries
+    pub fn max_weight(mut self, max_weight: u64) -> Self {
+        self.max_weight = Some(max_weight);
+        self
+    }
+
+    /// ttl of the values cached by the methods not taking one, cached() or put() among them
+    /// By default these values never expire
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Randomly shortens or lengthens each ttl by up to percent of it, 0 by default and capped at 100
+    /// Entries written together, after a deploy or a restore(), then don't all expire at once
+    pub fn ttl_jitter(mut self, percent: u8) -> Self {
+        self.ttl_jitter = percent.min(100);
+        self
+    }
+
+    /// What the computing methods do when their closure panics, PanicPolicy::Propagate by default
+    /// Covers every closure the cache calls itself; panics of the tasks spawned for the tokio methods
+    /// and of the streams given to cached_stream() are not caught, the former failing with CacheError::Join
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Longest time the async methods wait for the computation of a miss before dropping it and
+    /// failing with CacheError::Timeout, tasks of tokio_cached() being aborted
+    /// Computations of the sync methods can't be interrupted and are not bounded
+    /// Timing needs to run within a tokio runtime with its time driver enabled
+    #[cfg(feature = "tokio")]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Value of the `cache` label on the metrics of the cache, "default" by default
+    /// Give each cache its own name, caches reporting under the same one add up their gauges
+    #[cfg(feature = "metrics")]
+    pub fn metrics_name(mut self, name: impl Into<Arc<str>>) -> Self {
+        self.metrics_name = name.into();
+        self
+    }
+
+    /// Also reports hits and load durations per tag, under a `tag` label, off by default
+    /// Each tag becomes a separate series, keep it off when tags are built from arguments
+    #[cfg(feature = "metrics")]
+    pub fn metrics_tag_labels(mut self, enabled: bool) -> Self {
+        self.metrics_tag_labels = enabled;
+        self
+    }
+
+    /// Defaults to EvictionPolicy::Lru, only used with max_entries or max_bytes
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.eviction_policy = policy;
+        self
+    }
+
+    /// Defaults to KeyStrategy::Full, digests keep large arguments from being stored whole
+    /// With a digest, two arguments whose digests collide share an entry
+    pub fn key_strategy(mut self, strategy: KeyStrategy) -> Self {
+        self.key_strategy = strategy;
+        self
+    }
+
+    /// Compresses serialized values larger than the compression threshold, values are decompressed on read
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Size in bytes above which values are compressed, defaults to 1 KiB
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Second tier behind the local entries: misses are looked up in it, writes, removals and
+    /// invalidations are forwarded to it, so caches sharing a backend see each other's values
+    pub fn backend<B: CacheBackend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Publishes the tags given to invalidate() and applies the ones other instances publish
+    /// Received tags are invalidated before the next read of the cache
+    pub fn invalidation_transport<T: InvalidationTransport + 'static>(
+        mut self,
+        transport: T,
+    ) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Caps the ttl and the number of entries of tag, see DashmapCache::set_tag_policy()
+    pub fn tag_policy(mut self, tag: &str, policy: TagPolicy) -> Self {
+        self.tag_policies.insert(tag.to_owned(), policy);
+        self
+    }
+
+    /// Minimum time between two sweeps of expired entries, which run on writes
+    /// Defaults to one minute
+    pub fn sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    /// Checks the options build() would panic on, see try_build()
+    pub fn validate(&self) -> Result<(), BuildError> {
+        match self.shard_amount {
+            Some(shard_amount) if shard_amount < 2 || !shard_amount.is_power_of_two() => {
+                return Err(BuildError::InvalidShardAmount(shard_amount))
+            }
+            _ => {}
+        }
+        if self.max_entries == Some(0) {
+            return Err(BuildError::ZeroMaxEntries);
+        }
+        Ok(())
+    }
+
+    /// Panics if the shard amount or max_entries is invalid, see try_build() for a fallible version
+    pub fn build(self) -> DashmapCache<S> {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// build() returning the error validate() finds instead of panicking
+    pub fn try_build(self) -> Result<DashmapCache<S>, BuildError> {
+        self.validate()?;
+        Ok(DashmapCache::from_builder(self))
+    }
+
+    pub(crate) fn new_map<K: Eq + Hash, V>(&self, capacity: usize) -> DashMap<K, V> {
+        match self.shard_amount {
+            Some(shard_amount) => DashMap::with_capacity_and_shard_amount(capacity, shard_amount),
+            None => DashMap::with_capacity(capacity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether invalidating each of the old and new tags drops a key cached with the old tag and
+    /// refreshed with refresh_tags under policy
+    fn dropped_by(policy: RefreshTagPolicy, refresh_tags: &[&str]) -> (bool, bool) {
+        let refresh_tags: Vec<String> = refresh_tags.iter().map(|tag| tag.to_string()).collect();
+        let dropped = |tag: &str| {
+            let cache = DashmapCache::builder().refresh_tag_policy(policy).build();
+            cache
+                .cached(&vec!["old".to_owned()], |x: &u32| *x, 1)
+                .unwrap();
+            cache
+                .refresh_cache(&refresh_tags, |x: &u32| x + 1, 1)
+                .unwrap();
+            // A hit returns the refreshed value, a miss caches 0
+            assert_eq!(cache.cached(&vec![], |_| 0, 1u32).unwrap(), 2);
+            cache.invalidate(tag);
+            cache.cached(&vec![], |_| 0, 1u32).unwrap() == 0
+        };
+        (dropped("old"), dropped("new"))
+    }
+
+    #[test]
+    fn replace_keeps_only_the_refresh_tags() {
+        assert_eq!(dropped_by(RefreshTagPolicy::Replace, &[]), (false, false));
+        assert_eq!(
+            dropped_by(RefreshTagPolicy::Replace, &["new"]),
+            (false, true)
+        );
+    }
+
+    #[test]
+    fn preserve_keeps_only_the_previous_tags() {
+        assert_eq!(dropped_by(RefreshTagPolicy::Preserve, &[]), (true, false));
+        assert_eq!(
+            dropped_by(RefreshTagPolicy::Preserve, &["new"]),
+            (true, false)
+        );
+    }
+
+    #[test]
+    fn merge_keeps_both() {
+        assert_eq!(dropped_by(RefreshTagPolicy::Merge, &[]), (true, false));
+        assert_eq!(dropped_by(RefreshTagPolicy::Merge, &["new"]), (true, true));
+    }
+
+    #[test]
+    fn refresh_tags_apply_to_new_keys_whatever_the_policy() {
+        for policy in [
+            RefreshTagPolicy::Replace,
+            RefreshTagPolicy::Preserve,
+            RefreshTagPolicy::Merge,
+        ] {
+            let cache = DashmapCache::builder().refresh_tag_policy(policy).build();
+            cache
+                .refresh_cache(&vec!["new".to_owned()], |x: &u32| *x, 1)
+                .unwrap();
+            cache.invalidate("new");
+            assert_eq!(cache.cached(&vec![], |_| 0, 1u32).unwrap(), 0, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn zero_max_entries_is_rejected() {
+        let builder = DashmapCacheBuilder::new().max_entries(0);
+        assert!(matches!(
+            builder.validate(),
+            Err(BuildError::ZeroMaxEntries)
+        ));
+        let config = crate::CacheConfig {
+            max_entries: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(config.build(), Err(BuildError::ZeroMaxEntries)));
+        assert!(DashmapCacheBuilder::new().max_entries(1).validate().is_ok());
+        let built = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| builder.build()));
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn invalid_shard_amounts_fail_to_build() {
+        for shard_amount in [0, 1, 3, 12] {
+            let built = DashmapCacheBuilder::new()
+                .shard_amount(shard_amount)
+                .try_build();
+            assert!(
+                matches!(built, Err(BuildError::InvalidShardAmount(amount)) if amount == shard_amount)
+            );
+            assert!(DashmapCache::with_shard_amount(shard_amount).is_err());
+        }
+        let cache = DashmapCacheBuilder::new()
+            .shard_amount(8)
+            .try_build()
+            .unwrap();
+        assert_eq!(cache.cached(&vec![], |x| x + 1, 1u8).unwrap(), 2);
+        let built = std::panic::catch_unwind(|| DashmapCacheBuilder::new().shard_amount(3).build());
+        let message = built.unwrap_err();
+        assert_eq!(
+            message.downcast_ref::<String>().map(String::as_str),
+            Some("shard amount 3 is not a power of two greater than 1")
+        );
+    }
+}
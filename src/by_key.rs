@@ -0,0 +1,57 @@
+use core::future::Future;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Same as cached(), keyed on key_fn(&arg) instead of arg, which then needs no Serialize
+    /// Lets arguments holding connections or channels be cached on the part of them that matters,
+    /// calls whose key_fn returns the same key sharing an entry, with cached() calls of that key as well
+    pub fn cached_by_key<KF, K, F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        key_fn: KF,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        KF: Fn(&A) -> K,
+        K: Serialize,
+        F: Fn(&A) -> V,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cached_at(
+            self.key_of(&key_fn(&arg))?,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+    }
+
+    /// Async version of cached_by_key()
+    pub async fn async_cached_by_key<KF, K, F, Fut, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        key_fn: KF,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        KF: Fn(&A) -> K,
+        K: Serialize,
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.async_cached_at(
+            self.key_of(&key_fn(&arg))?,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+        .await
+    }
+}
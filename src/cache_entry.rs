@@ -0,0 +1,155 @@
+use core::ops::Deref;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::entry::{Entry, Expiry};
+use crate::lock::KeyGuard;
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, MsgPack, Serializer};
+
+/// Locked view of the cache entry of an arg, obtained from DashmapCache::entry()
+/// The key stays locked until the entry, or the value guard it returns, is dropped: cached() misses,
+/// puts and refreshes of the key wait meanwhile, while reads go on
+/// Calling back into the cache for the same key while holding it deadlocks
+#[derive(Debug)]
+pub struct CacheEntry<'a, S: Serializer = MsgPack> {
+    cache: &'a DashmapCache<S>,
+    key: Vec<u8>,
+    tags: Vec<String>,
+    flight: KeyGuard<'a, S>,
+}
+
+/// Value read or inserted through a CacheEntry, keeping its key locked until dropped
+#[derive(Debug)]
+pub struct EntryValue<'a, V, S: Serializer = MsgPack> {
+    value: V,
+    _flight: KeyGuard<'a, S>,
+}
+
+impl<V, S: Serializer> EntryValue<'_, V, S> {
+    /// Releases the key lock and returns the value
+    pub fn into_inner(self) -> V {
+        self.value
+    }
+}
+
+impl<V, S: Serializer> Deref for EntryValue<'_, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Locks the key computed for arg and returns its entry, waiting for any other holder first
+    pub fn entry<A: Serialize>(&self, arg: &A) -> Result<CacheEntry<'_, S>, CacheError> {
+        let key = self.key_of(arg)?;
+        Ok(CacheEntry {
+            cache: self,
+            flight: self.lock_key_at(key.clone()),
+            key,
+            tags: vec![],
+        })
+    }
+}
+
+impl<'a, S: Serializer> CacheEntry<'a, S> {
+    /// Tags attached to a value inserted through the entry, none by default
+    pub fn tagged(mut self, invalidate_keys: Vec<String>) -> Self {
+        self.tags = invalidate_keys;
+        self
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Whether a live value is cached for the key
+    pub fn is_occupied(&self) -> bool {
+        self.cache.live_entry(&self.key).is_some()
+    }
+
+    /// Cached value for the key, if any
+    pub fn get<V: DeserializeOwned>(&self) -> Result<Option<V>, CacheError> {
+        self.cache.lookup(&self.key)
+    }
+
+    /// The cached value, or value once it is cached if there was none
+    pub fn or_insert<V>(self, value: V) -> Result<EntryValue<'a, V, S>, CacheError>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        self.or_insert_with(|| value)
+    }
+
+    /// The cached value, or the value computed by f once it is cached if there was none
+    pub fn or_insert_with<V, F>(self, f: F) -> Result<EntryValue<'a, V, S>, CacheError>
+    where
+        F: FnOnce() -> V,
+        V: Serialize + DeserializeOwned,
+    {
+        self.or_try_insert_with(|| Ok::<_, CacheError>(f()))
+    }
+
+    /// Same as or_insert_with() for a fallible f, nothing being cached when it fails
+    pub fn or_try_insert_with<V, E, F>(self, f: F) -> Result<EntryValue<'a, V, S>, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+        V: Serialize + DeserializeOwned,
+        E: From<CacheError>,
+    {
+        if let Some(value) = self.get()? {
+            self.cache.record_tag_hit(&self.tags);
+            return Ok(self.guard(value));
+        }
+        let store = self.cache.value_store(self.cache.default_expiry());
+        let (value, _outcome) = self
+            .cache
+            .fill_miss(&self.key, &self.tags, false, f, store)?;
+        Ok(self.guard(value))
+    }
+
+    /// Caches value for the key, replacing any value it had
+    pub fn insert<V: Serialize>(self, value: V) -> Result<EntryValue<'a, V, S>, CacheError> {
+        let entry = Entry::new(self.cache.encode(&value)?, self.cache.default_expiry());
+        self.cache.insert(&self.tags, &self.key, entry);
+        Ok(self.guard(value))
+    }
+
+    /// Runs f on the cached value, if any, and caches what it leaves in place of it
+    /// The value keeps the time it had left, and the tags attached to the key are kept along with the entry ones
+    pub fn and_modify<V, F>(self, f: F) -> Result<Self, CacheError>
+    where
+        F: FnOnce(&mut V),
+        V: Serialize + DeserializeOwned,
+    {
+        let Some((stored, expires_at)) = self
+            .cache
+            .live_entry(&self.key)
+            .map(|entry| (entry.value.clone(), entry.expires_at))
+        else {
+            return Ok(self);
+        };
+        StatCounters::incr(&self.cache.stats.hits, 1);
+        let mut value: V = self.cache.decode(&stored)?;
+        f(&mut value);
+        let ttl = expires_at.map(|expires_at| expires_at.saturating_duration_since(Instant::now()));
+        let entry = Entry::new(self.cache.encode(&value)?, Expiry::from_ttl(ttl));
+        self.cache.insert(&self.tags, &self.key, entry);
+        Ok(self)
+    }
+
+    /// Removes the value cached for the key, returns whether there was one
+    pub fn remove(self) -> bool {
+        self.cache.remove_key(&self.key)
+    }
+
+    fn guard<V>(self, value: V) -> EntryValue<'a, V, S> {
+        EntryValue {
+            value,
+            _flight: self.flight,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use core::future::Future;
+#[cfg(feature = "tokio")]
+use core::pin::Pin;
+#[cfg(feature = "tokio")]
+use core::task::{Context, Poll};
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// JoinHandle aborting its task when dropped, so that a cancelled or timed out tokio_cached() call
+/// doesn't leave the computation running for a value nobody will cache
+#[cfg(feature = "tokio")]
+pub(crate) struct AbortOnDrop<T>(pub(crate) tokio::task::JoinHandle<T>);
+
+#[cfg(feature = "tokio")]
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, tokio::task::JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Awaits the computation of a miss, dropping it with CacheError::Timeout past the builder timeout
+    pub(crate) async fn timed<Fut, V, E>(&self, computation: Fut) -> Result<V, E>
+    where
+        Fut: Future<Output = Result<V, E>>,
+        E: From<CacheError>,
+    {
+        #[cfg(feature = "tokio")]
+        if let Some(timeout) = self.timeout {
+            return tokio::time::timeout(timeout, computation)
+                .await
+                .map_err(|_elapsed| CacheError::Timeout)?;
+        }
+        computation.await
+    }
+}
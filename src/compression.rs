@@ -0,0 +1,184 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Tags starting every stored value once compression is enabled
+pub(crate) const RAW: u8 = 0;
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 2;
+
+/// Codec applied to serialized values larger than the compression threshold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    /// LZ4 block compression, fast with a moderate ratio
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// zstd at the given level, 0 being zstd's default
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Whether stored values start with a compression tag
+    pub(crate) fn framed(&self) -> bool {
+        self.compression != Compression::None
+    }
+
+    /// Serializes val the way it is stored, compressed if it is large enough
+    pub(crate) fn encode<V: Serialize + ?Sized>(&self, val: &V) -> Result<Vec<u8>, CacheError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("encode").entered();
+        let mut buf = Vec::new();
+        self.encode_into(val, &mut buf)?;
+        Ok(self.compressed(&buf)?.unwrap_or(buf))
+    }
+
+    /// Appends the uncompressed stored form of val to buf, see compressed()
+    pub(crate) fn encode_into<V: Serialize + ?Sized>(
+        &self,
+        val: &V,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError> {
+        if self.framed() {
+            buf.push(RAW);
+        }
+        self.serializer.encode_into(val, buf)
+    }
+
+    /// Compressed version of a value written by encode_into(), None if it should be stored as it is
+    #[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn compressed(&self, stored: &[u8]) -> Result<Option<Vec<u8>>, CacheError> {
+        let raw = match stored.split_first() {
+            Some((_tag, raw)) if self.framed() && raw.len() > self.compression_threshold => raw,
+            _ => return Ok(None),
+        };
+        let packed: Option<Vec<u8>> = match self.compression {
+            Compression::None => None,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                let mut packed = vec![LZ4];
+                packed.extend_from_slice(&lz4_flex::compress_prepend_size(raw));
+                Some(packed)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                let mut packed = vec![ZSTD];
+                zstd::stream::copy_encode(raw, &mut packed, level)?;
+                Some(packed)
+            }
+        };
+        Ok(packed.filter(|packed| packed.len() < stored.len()))
+    }
+
+    /// Reverse of encode(), whatever compression the value was stored with
+    pub(crate) fn decode<V: DeserializeOwned>(&self, stored: &[u8]) -> Result<V, CacheError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("decode", bytes = stored.len()).entered();
+        self.serializer.decode(&self.raw_value(stored)?)
+    }
+
+    /// Bytes the serializer wrote for a stored value, borrowed unless they were compressed
+    pub(crate) fn raw_value<'a>(&self, stored: &'a [u8]) -> Result<Cow<'a, [u8]>, CacheError> {
+        if !self.framed() {
+            return Ok(Cow::Borrowed(stored));
+        }
+        match stored.split_first() {
+            Some((&RAW, raw)) => Ok(Cow::Borrowed(raw)),
+            #[cfg(feature = "lz4")]
+            Some((&LZ4, packed)) => lz4_flex::decompress_size_prepended(packed)
+                .map(Cow::Owned)
+                .map_err(|e| CacheError::Codec(Box::new(e))),
+            #[cfg(feature = "zstd")]
+            Some((&ZSTD, packed)) => Ok(Cow::Owned(zstd::stream::decode_all(packed)?)),
+            _ => Err(CacheError::Codec("unknown compression tag".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: usize = 64;
+
+    fn cache(compression: Compression) -> DashmapCache {
+        DashmapCache::builder()
+            .compression(compression)
+            .compression_threshold(THRESHOLD)
+            .build()
+    }
+
+    /// Stored form of a value just under the threshold and of one well above it
+    fn round_trip(cache: &DashmapCache) -> (Vec<u8>, Vec<u8>) {
+        let small = "a".repeat(THRESHOLD - 8);
+        let large = "a".repeat(THRESHOLD * 16);
+        let stored_small = cache.encode(&small).unwrap();
+        let stored_large = cache.encode(&large).unwrap();
+        assert_eq!(cache.decode::<String>(&stored_small).unwrap(), small);
+        assert_eq!(cache.decode::<String>(&stored_large).unwrap(), large);
+        (stored_small, stored_large)
+    }
+
+    #[test]
+    fn values_are_stored_unframed_without_compression() {
+        let cache = cache(Compression::None);
+        let (small, large) = round_trip(&cache);
+        assert_eq!(
+            small,
+            rmp_serde::to_vec(&"a".repeat(THRESHOLD - 8)).unwrap()
+        );
+        assert_eq!(
+            large,
+            rmp_serde::to_vec(&"a".repeat(THRESHOLD * 16)).unwrap()
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_frames_round_trip() {
+        let cache = cache(Compression::Lz4);
+        let (small, large) = round_trip(&cache);
+        assert_eq!(small[0], RAW);
+        assert_eq!(large[0], LZ4);
+        assert!(large.len() < THRESHOLD * 16);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_frames_round_trip() {
+        let cache = cache(Compression::Zstd(0));
+        let (small, large) = round_trip(&cache);
+        assert_eq!(small[0], RAW);
+        assert_eq!(large[0], ZSTD);
+        assert!(large.len() < THRESHOLD * 16);
+    }
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    #[test]
+    fn unknown_frames_are_errors() {
+        #[cfg(feature = "lz4")]
+        let cache = cache(Compression::Lz4);
+        #[cfg(not(feature = "lz4"))]
+        let cache = cache(Compression::Zstd(0));
+        let mut stored = cache.encode(&"a".repeat(THRESHOLD * 16)).unwrap();
+        stored[0] = 0xff;
+        assert!(matches!(
+            cache.decode::<String>(&stored),
+            Err(CacheError::Codec(_))
+        ));
+        assert!(matches!(
+            cache.decode::<String>(&[]),
+            Err(CacheError::Codec(_))
+        ));
+        // A known tag in front of bytes it didn't write fails to decompress instead of panicking
+        stored[0] = cache.encode(&"a".repeat(THRESHOLD * 16)).unwrap()[0];
+        stored.truncate(4);
+        assert!(cache.decode::<String>(&stored).is_err());
+    }
+}
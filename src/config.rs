@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    BuildError, Compression, DashmapCache, DashmapCacheBuilder, EvictionPolicy, KeyStrategy,
+    PanicPolicy, RefreshTagPolicy, TagPolicy,
+};
+
+/// Cache settings that can be loaded from a configuration file
+/// Missing fields take the same defaults as DashmapCacheBuilder
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub shard_amount: Option<usize>,
+    pub initial_capacity: usize,
+    pub initial_tag_capacity: usize,
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub max_weight: Option<u64>,
+    /// Durations are written as "30s", "250ms", "5m" and so on, or as a number of seconds
+    #[serde(with = "crate::duration::option")]
+    pub default_ttl: Option<Duration>,
+    pub ttl_jitter: u8,
+    pub panic_policy: PanicPolicy,
+    #[cfg(feature = "tokio")]
+    #[serde(with = "crate::duration::option")]
+    pub timeout: Option<Duration>,
+    #[serde(with = "crate::duration::option")]
+    pub sweep_interval: Option<Duration>,
+    pub eviction_policy: EvictionPolicy,
+    pub key_strategy: KeyStrategy,
+    pub compression: Compression,
+    pub compression_threshold: Option<usize>,
+    pub refresh_tag_policy: RefreshTagPolicy,
+    pub tag_policies: HashMap<String, TagPolicy>,
+    pub return_value_on_cache_error: bool,
+}
+
+impl CacheConfig {
+    pub fn builder(&self) -> DashmapCacheBuilder {
+        let mut builder = DashmapCacheBuilder::new()
+            .initial_capacity(self.initial_capacity)
+            .initial_tag_capacity(self.initial_tag_capacity)
+            .eviction_policy(self.eviction_policy)
+            .key_strategy(self.key_strategy)
+            .compression(self.compression)
+            .refresh_tag_policy(self.refresh_tag_policy)
+            .ttl_jitter(self.ttl_jitter)
+            .panic_policy(self.panic_policy)
+            .return_value_on_cache_error(self.return_value_on_cache_error);
+        if let Some(max_entries) = self.max_entries {
+            builder = builder.max_entries(max_entries);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            builder = builder.max_bytes(max_bytes);
+        }
+        if let Some(max_weight) = self.max_weight {
+            builder = builder.max_weight(max_weight);
+        }
+        if let Some(ttl) = self.default_ttl {
+            builder = builder.default_ttl(ttl);
+        }
+        #[cfg(feature = "tokio")]
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(threshold) = self.compression_threshold {
+            builder = builder.compression_threshold(threshold);
+        }
+        for (tag, policy) in &self.tag_policies {
+            builder = builder.tag_policy(tag, *policy);
+        }
+        if let Some(interval) = self.sweep_interval {
+            builder = builder.sweep_interval(interval);
+        }
+        if let Some(shard_amount) = self.shard_amount {
+            builder = builder.shard_amount(shard_amount);
+        }
+        builder
+    }
+
+    pub fn build(self) -> Result<DashmapCache, BuildError> {
+        self.builder().try_build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_a_working_cache_from_json() {
+        let config: CacheConfig = serde_json::from_str(
+            r#"{
+                "max_entries": 2,
+                "default_ttl": "50ms",
+                "sweep_interval": "1s",
+                "panic_policy": {"CacheFor": "5m"},
+                "tag_policies": {"short": {"ttl": 0.01}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.default_ttl, Some(Duration::from_millis(50)));
+        assert_eq!(config.sweep_interval, Some(Duration::from_secs(1)));
+        assert_eq!(
+            config.panic_policy,
+            PanicPolicy::CacheFor(Duration::from_secs(300))
+        );
+        assert_eq!(
+            config.tag_policies["short"].ttl,
+            Some(Duration::from_millis(10))
+        );
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["default_ttl"], "50ms");
+        assert_eq!(serde_json::from_value::<CacheConfig>(json).unwrap(), config);
+
+        let cache = config.build().unwrap();
+        assert_eq!(cache.cached(&vec![], |x: &u32| x * 2, 1).unwrap(), 2);
+        assert_eq!(cache.cached(&vec![], |x: &u32| x * 3, 1).unwrap(), 2);
+        for x in [2u32, 3] {
+            cache.cached(&vec![], |x: &u32| *x, x).unwrap();
+        }
+        assert_eq!(cache.inner.len(), 2);
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.cached(&vec![], |x: &u32| x * 3, 3).unwrap(), 9);
+    }
+
+    #[test]
+    fn rejects_unknown_duration_units() {
+        let err = serde_json::from_str::<CacheConfig>(r#"{"default_ttl": "5 weeks"}"#).unwrap_err();
+        assert!(err.to_string().contains("unknown unit"), "{err}");
+    }
+
+    #[test]
+    fn rejects_invalid_shard_amounts() {
+        let config: CacheConfig = serde_json::from_str(r#"{"shard_amount": 3}"#).unwrap();
+        assert!(matches!(
+            config.build(),
+            Err(BuildError::InvalidShardAmount(3))
+        ));
+    }
+}
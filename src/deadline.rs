@@ -0,0 +1,120 @@
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::marker::{Send, Sync};
+use std::time::{Duration, Instant};
+
+use crate::entry::{Entry, Expiry};
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Random number in [-1, 1)
+fn unit_noise() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Spreads the deadlines of entry by up to the builder ttl_jitter, both by the same factor
+    pub(crate) fn jitter_ttl(&self, entry: &mut Entry) {
+        if self.ttl_jitter == 0 || entry.expires_at.is_none() {
+            return;
+        }
+        let factor = 1.0 + unit_noise() * f64::from(self.ttl_jitter) / 100.0;
+        let now = Instant::now();
+        let scale = |at: Instant| now + at.saturating_duration_since(now).mul_f64(factor);
+        entry.expires_at = entry.expires_at.map(scale);
+        entry.stale_at = entry.stale_at.map(scale);
+    }
+
+    /// Same as cached(), with a soft and a hard deadline instead of a single ttl
+    /// Past soft, the first call to get the key lock recomputes the value while the others keep
+    /// being served the stale one; past hard, the entry is gone and calls wait like on a miss
+    /// A hard deadline below soft is taken as soft
+    /// Each call counts as one hit or one miss, a call recomputing a stale value being a miss
+    pub fn cached_with_deadlines<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        soft: Duration,
+        hard: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let key = self.key_of(&arg)?;
+        let expiry = Expiry {
+            ttl: Some(soft),
+            stale_ttl: hard.saturating_sub(soft),
+        };
+        let stale = self
+            .live_entry(&key)
+            .map(|entry| entry.is_stale(Instant::now()));
+        match stale {
+            None => return self.cached_at(key, invalidate_keys, expiry, closure, arg),
+            Some(true) => {
+                if let Some(_flight) = self.try_lock_key_at(key.clone()) {
+                    // The call that held the lock until now may have refreshed the value already
+                    let refreshed = self
+                        .live_entry(&key)
+                        .is_some_and(|entry| !entry.is_stale(Instant::now()));
+                    if !refreshed {
+                        let (val, _outcome) = self.fill_miss(
+                            &key,
+                            invalidate_keys,
+                            true,
+                            || Ok::<_, CacheError>(closure(&arg)),
+                            self.value_store(expiry),
+                        )?;
+                        return Ok(val);
+                    }
+                }
+            }
+            Some(false) => {}
+        }
+        match self.lookup::<V>(&key)? {
+            Some(val) => {
+                self.record_tag_hit(invalidate_keys);
+                Ok(val)
+            }
+            None => self.cached_at(key, invalidate_keys, expiry, closure, arg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_call_counts_once() {
+        let cache = DashmapCache::new();
+        let soft = Duration::from_millis(20);
+        let hard = Duration::from_secs(60);
+        let call = |add: u64| {
+            cache
+                .cached_with_deadlines(&vec![], soft, hard, |x| x + add, 1u64)
+                .unwrap()
+        };
+        assert_eq!(call(1), 2);
+        assert_eq!(call(2), 2);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        std::thread::sleep(soft * 2);
+        let key = cache.key_of(&1u64).unwrap();
+        let flight = cache.lock_key_at(key);
+        assert_eq!(call(3), 2);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        drop(flight);
+        assert_eq!(call(4), 5);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(call(5), 5);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (3, 2));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use crate::{DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Makes invalidating dependency invalidate dependent too, and in turn the tags depending on dependent
+    /// Cycles are allowed, each tag is invalidated once per call
+    pub fn add_tag_dependency(&self, dependent: &str, dependency: &str) {
+        self.tag_dependents
+            .entry(dependency.to_owned())
+            .or_default()
+            .insert(dependent.to_owned());
+    }
+
+    /// Returns whether dependent depended on dependency
+    pub fn remove_tag_dependency(&self, dependent: &str, dependency: &str) -> bool {
+        let Some(dependents) = self.tag_dependents.get(dependency) else {
+            return false;
+        };
+        let removed = dependents.remove(dependent).is_some();
+        drop(dependents);
+        self.tag_dependents
+            .remove_if(dependency, |_tag, dependents| dependents.is_empty());
+        removed
+    }
+
+    /// tag followed by every tag depending on it, directly or not
+    pub(crate) fn with_dependents(&self, tag: &str) -> Vec<String> {
+        let mut tags = vec![tag.to_owned()];
+        if self.tag_dependents.is_empty() {
+            return tags;
+        }
+        let mut seen: HashSet<String> = tags.iter().cloned().collect();
+        let mut next = 0;
+        while let Some(tag) = tags.get(next) {
+            if let Some(dependents) = self.tag_dependents.get(tag) {
+                let new: Vec<String> = dependents
+                    .iter()
+                    .filter(|dependent| !seen.contains(dependent.key()))
+                    .map(|dependent| dependent.key().clone())
+                    .collect();
+                drop(dependents);
+                seen.extend(new.iter().cloned());
+                tags.extend(new);
+            }
+            next += 1;
+        }
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_tagged(tags: &[&str]) -> DashmapCache {
+        let cache = DashmapCache::new();
+        for tag in tags {
+            cache.put(&vec![tag.to_string()], tag, &1u8).unwrap();
+        }
+        cache
+    }
+
+    #[test]
+    fn invalidations_cascade_to_dependents() {
+        let cache = cache_tagged(&["org", "team", "user", "unrelated"]);
+        cache.add_tag_dependency("team", "org");
+        cache.add_tag_dependency("user", "team");
+        cache.invalidate("team");
+        assert!(cache.contains(&"org").unwrap());
+        assert!(!cache.contains(&"team").unwrap());
+        assert!(!cache.contains(&"user").unwrap());
+
+        let cache = cache_tagged(&["org", "team", "user", "unrelated"]);
+        cache.add_tag_dependency("team", "org");
+        cache.add_tag_dependency("user", "team");
+        cache.invalidate("org");
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(&"unrelated").unwrap());
+    }
+
+    #[test]
+    fn removed_dependencies_stop_cascading() {
+        let cache = cache_tagged(&["org", "team"]);
+        cache.add_tag_dependency("team", "org");
+        assert!(cache.remove_tag_dependency("team", "org"));
+        assert!(!cache.remove_tag_dependency("team", "org"));
+        assert!(cache.tag_dependents.is_empty());
+        cache.invalidate("org");
+        assert!(cache.contains(&"team").unwrap());
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let cache = cache_tagged(&["a", "b", "c"]);
+        cache.add_tag_dependency("b", "a");
+        cache.add_tag_dependency("a", "b");
+        cache.add_tag_dependency("a", "a");
+        assert_eq!(cache.with_dependents("a"), ["a", "b"]);
+        assert_eq!(cache.with_dependents("b"), ["b", "a"]);
+        let invalidations = cache.stats().invalidations;
+        cache.invalidate("a");
+        assert_eq!(cache.stats().invalidations, invalidations + 2);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(&"c").unwrap());
+    }
+}
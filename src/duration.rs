@@ -0,0 +1,174 @@
+use core::fmt;
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use std::time::Duration;
+
+const UNITS: [(&str, f64); 7] = [
+    ("ns", 1e-9),
+    ("us", 1e-6),
+    ("ms", 1e-3),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 3600.0),
+    ("d", 86400.0),
+];
+
+/// Parses a duration of the configuration types, written as `"250ms"`, `"30s"`, `"5m"`, `"2h"` or `"1d"`
+/// A bare number is read as seconds, fractional values such as `"1.5s"` being accepted
+pub(crate) fn parse(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration {text:?}"))?;
+    let unit = match unit.trim() {
+        "" => 1.0,
+        unit => {
+            UNITS
+                .iter()
+                .find(|(name, _secs)| *name == unit)
+                .ok_or_else(|| {
+                    format!(
+                    "unknown unit {unit:?} in duration {text:?}, use one of ns, us, ms, s, m, h, d"
+                )
+                })?
+                .1
+        }
+    };
+    Duration::try_from_secs_f64(value * unit)
+        .map_err(|err| format!("invalid duration {text:?}: {err}"))
+}
+
+/// Writes duration in the largest unit it is a whole number of
+pub(crate) fn format(duration: &Duration) -> String {
+    let nanos = duration.as_nanos();
+    let (name, unit) = [
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+    ]
+    .into_iter()
+    .find(|(_name, unit)| nanos > 0 && nanos.is_multiple_of(*unit))
+    .unwrap_or(("ns", 1));
+    format!("{}{name}", nanos / unit)
+}
+
+struct DurationVisitor;
+
+impl Visitor<'_> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a duration such as \"30s\" or \"250ms\", or a number of seconds")
+    }
+
+    fn visit_u64<E: de::Error>(self, secs: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(secs))
+    }
+
+    fn visit_i64<E: de::Error>(self, secs: i64) -> Result<Duration, E> {
+        u64::try_from(secs)
+            .map(Duration::from_secs)
+            .map_err(|_| E::custom("a duration can't be negative"))
+    }
+
+    fn visit_f64<E: de::Error>(self, secs: f64) -> Result<Duration, E> {
+        Duration::try_from_secs_f64(secs).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Duration, E> {
+        parse(text).map_err(E::custom)
+    }
+}
+
+pub(crate) fn serialize<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(duration))
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+/// Same format for an `Option<Duration>`, null being None
+pub(crate) mod option {
+    use super::*;
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Duration>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            DurationVisitor.expecting(f)
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&format(duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_each_unit() {
+        assert_eq!(parse("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse(" 5m "), Ok(Duration::from_secs(300)));
+        assert_eq!(parse("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse("30"), Ok(Duration::from_secs(30)));
+        assert!(parse("5 weeks").is_err());
+        assert!(parse("s").is_err());
+        for duration in [
+            Duration::ZERO,
+            Duration::from_nanos(7),
+            Duration::from_millis(1500),
+            Duration::from_secs(300),
+            Duration::from_secs(86400 * 3),
+        ] {
+            assert_eq!(parse(&format(&duration)), Ok(duration));
+        }
+        assert_eq!(format(&Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format(&Duration::from_secs(120)), "2m");
+    }
+}
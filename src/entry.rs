@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// When an entry stops being fresh and when it is dropped
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Expiry {
+    pub(crate) ttl: Option<Duration>,
+    /// Time an entry older than ttl is still served while it gets recomputed
+    pub(crate) stale_ttl: Duration,
+}
+
+impl Expiry {
+    pub(crate) const NEVER: Expiry = Expiry {
+        ttl: None,
+        stale_ttl: Duration::ZERO,
+    };
+
+    pub(crate) fn after(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            stale_ttl: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn from_ttl(ttl: Option<Duration>) -> Self {
+        ttl.map_or(Self::NEVER, Self::after)
+    }
+}
+
+/// Expiry of a value, which may depend on the value itself
+pub(crate) trait ExpiryOf<V> {
+    fn expiry_of(&self, val: &V) -> Expiry;
+}
+
+impl<V> ExpiryOf<V> for Expiry {
+    fn expiry_of(&self, _val: &V) -> Expiry {
+        *self
+    }
+}
+
+impl<V, F: Fn(&V) -> Expiry> ExpiryOf<V> for F {
+    fn expiry_of(&self, val: &V) -> Expiry {
+        self(val)
+    }
+}
+
+/// Serialized value stored for a key, with the bookkeeping needed to expire and evict it
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) value: Vec<u8>,
+    pub(crate) expires_at: Option<Instant>,
+    /// Past this point the value is served while being recomputed, see cached_swr()
+    pub(crate) stale_at: Option<Instant>,
+    /// When the value was stored, see cached_with_info()
+    pub(crate) created_at: Instant,
+    /// Reverse index of the tag sets listing the key
+    pub(crate) tags: Vec<String>,
+    /// Tick of the cache access clock at the last read or write
+    pub(crate) last_access: AtomicU64,
+    pub(crate) hits: AtomicU64,
+    /// Share of max_weight taken by the entry, 1 unless cached with cached_weighted()
+    pub(crate) weight: u64,
+    /// Priority under EvictionPolicy::Weighted, see DashmapCache::touch()
+    pub(crate) credit: AtomicU64,
+}
+
+impl Clone for Entry {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            expires_at: self.expires_at,
+            stale_at: self.stale_at,
+            created_at: self.created_at,
+            tags: self.tags.clone(),
+            last_access: AtomicU64::new(self.last_access.load(Ordering::Relaxed)),
+            hits: AtomicU64::new(self.hits.load(Ordering::Relaxed)),
+            weight: self.weight,
+            credit: AtomicU64::new(self.credit.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Entry {
+    pub(crate) fn new(value: Vec<u8>, expiry: Expiry) -> Self {
+        let now = Instant::now();
+        let stale_at = expiry.ttl.map(|ttl| now + ttl);
+        Self {
+            value,
+            expires_at: stale_at.map(|stale_at| stale_at + expiry.stale_ttl),
+            stale_at: stale_at.filter(|_| !expiry.stale_ttl.is_zero()),
+            created_at: now,
+            tags: Vec::new(),
+            last_access: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            weight: 1,
+            credit: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn with_weight(mut self, weight: u64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub(crate) fn is_stale(&self, now: Instant) -> bool {
+        self.stale_at.is_some_and(|stale_at| stale_at <= now)
+    }
+
+    /// Bytes accounted for the entry stored under key
+    pub(crate) fn size(&self, key: &[u8]) -> usize {
+        key.len() + self.value.len()
+    }
+
+    /// Records a cache hit at tick
+    pub(crate) fn touch(&self, tick: u64) {
+        self.last_access.store(tick, Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+}
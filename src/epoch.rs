@@ -0,0 +1,83 @@
+use dashmap::DashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of tag epoch counters, tags sharing one when their hashes fall in the same slot
+const SLOTS: usize = 256;
+
+/// Invalidation generation of the tags, bumped by every invalidation of a tag
+/// Tags are hashed into a fixed set of counters so that the memory used stays the same whatever
+/// the number of tags ever invalidated; invalidating a tag bumps the other tags of its slot as well,
+/// which only costs the values computed for them meanwhile being discarded instead of cached
+/// Tags whose epoch is read through DashmapCache::epoch() get an exact counter besides
+#[derive(Debug)]
+pub(crate) struct TagEpochs {
+    slots: Box<[AtomicU64]>,
+    /// Invalidations of each watched tag since it was first watched
+    watched: DashMap<String, u64>,
+}
+
+impl Default for TagEpochs {
+    fn default() -> Self {
+        Self {
+            slots: (0..SLOTS).map(|_| AtomicU64::new(0)).collect(),
+            watched: DashMap::new(),
+        }
+    }
+}
+
+impl Clone for TagEpochs {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self
+                .slots
+                .iter()
+                .map(|epoch| AtomicU64::new(epoch.load(Ordering::Relaxed)))
+                .collect(),
+            watched: self.watched.clone(),
+        }
+    }
+}
+
+impl TagEpochs {
+    fn slot(&self, tag: &str) -> &AtomicU64 {
+        let mut hasher = DefaultHasher::new();
+        tag.hash(&mut hasher);
+        &self.slots[hasher.finish() as usize % SLOTS]
+    }
+
+    pub(crate) fn get(&self, tag: &str) -> u64 {
+        self.slot(tag).load(Ordering::Acquire)
+    }
+
+    pub(crate) fn bump(&self, tag: &str) {
+        self.slot(tag).fetch_add(1, Ordering::AcqRel);
+        if let Some(mut invalidations) = self.watched.get_mut(tag) {
+            *invalidations += 1;
+        }
+    }
+
+    /// Exact count of the invalidations of tag, counted from the first call for it on
+    pub(crate) fn watch(&self, tag: &str) -> u64 {
+        if let Some(invalidations) = self.watched.get(tag) {
+            return *invalidations;
+        }
+        *self.watched.entry(tag.to_owned()).or_insert(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_stays_bounded() {
+        let epochs = TagEpochs::default();
+        for i in 0..10_000 {
+            epochs.bump(&format!("tag-{i}"));
+        }
+        assert_eq!(epochs.slots.len(), SLOTS);
+        assert!(epochs.get("tag-1") >= 1);
+        assert_eq!(epochs.clone().get("tag-1"), epochs.get("tag-1"));
+    }
+}
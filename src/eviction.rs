@@ -0,0 +1,213 @@
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+use crate::entry::Entry;
+use crate::stats::StatCounters;
+use crate::{DashmapCache, Serializer};
+
+/// Which entries a bounded cache drops first once it holds more than max_entries, max_bytes or max_weight
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    /// Least recently read or written
+    #[default]
+    Lru,
+    /// Least often read, ties broken by recency
+    Lfu,
+    /// GreedyDual: heavier entries, cached with a larger cached_weighted() weight, are kept longer
+    /// Each eviction ages the remaining entries, so heavy entries that are no longer read still go eventually
+    Weighted,
+}
+
+/// Why an entry left the cache, see DashmapCache::on_evict()
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EvictionReason {
+    /// Its ttl elapsed
+    Expired,
+    /// One of its tags was invalidated, or the cache was cleared
+    Invalidated,
+    /// Dropped to respect max_entries, max_bytes, max_weight or a tag max_entries
+    CapacityEvicted,
+    /// A new value was stored under the same key
+    Replaced,
+    /// Removed by remove()
+    Removed,
+}
+
+type EvictionHook = Arc<dyn Fn(&[u8], &[u8], EvictionReason) + Send + Sync>;
+
+/// Hooks registered with on_evict(), shared by clones of the cache
+#[derive(Clone, Default)]
+pub(crate) struct EvictionHooks(Arc<RwLock<Vec<EvictionHook>>>);
+
+impl fmt::Debug for EvictionHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hooks = self.0.read().unwrap_or_else(|e| e.into_inner()).len();
+        f.debug_tuple("EvictionHooks").field(&hooks).finish()
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Calls hook with the key, the value bytes and the reason of every entry leaving the cache
+    /// Value bytes are the stored ones, compressed if compression applied to them
+    /// Hooks run once the entry is removed, outside of the cache locks
+    pub fn on_evict<F>(&self, hook: F)
+    where
+        F: Fn(&[u8], &[u8], EvictionReason) + Send + Sync + 'static,
+    {
+        self.evict_hooks
+            .0
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::new(hook));
+    }
+
+    pub(crate) fn notify_evicted(&self, key: &[u8], value: &[u8], reason: EvictionReason) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(key = crate::instrument::key_hash(key), ?reason, "removed");
+        // Cloned so that the lock is released before the hooks run, letting them call back into the cache
+        let hooks = self
+            .evict_hooks
+            .0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for hook in hooks {
+            hook(key, value, reason);
+        }
+    }
+
+    pub(crate) fn has_evict_hooks(&self) -> bool {
+        !self
+            .evict_hooks
+            .0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    /// Tick of the logical clock used to order accesses
+    pub(crate) fn tick(&self) -> u64 {
+        self.access_clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Records a hit on entry
+    pub(crate) fn touch(&self, entry: &Entry) {
+        entry.touch(self.tick());
+        if self.eviction_policy == EvictionPolicy::Weighted {
+            entry.credit.store(self.credit_of(entry), Ordering::Relaxed);
+        }
+    }
+
+    /// GreedyDual priority of an entry read or written now
+    pub(crate) fn credit_of(&self, entry: &Entry) -> u64 {
+        self.inflation
+            .load(Ordering::Relaxed)
+            .saturating_add(entry.weight)
+    }
+
+    fn over_limits(&self) -> bool {
+        self.max_entries.is_some_and(|max| self.inner.len() > max)
+            || self
+                .max_bytes
+                .is_some_and(|max| self.approx_bytes() > max as u64)
+            || self.max_weight.is_some_and(|max| self.total_weight() > max)
+    }
+
+    /// Brings a bounded cache back under max_entries, max_bytes and max_weight
+    /// Expired entries go first, then entries are dropped in eviction policy order until about 1/16 of
+    /// each limit is free, so the full scan this takes is not repeated on every insert at capacity
+    /// The key just written, if any, is kept: under Lfu its lone hit count would make it the first to go
+    pub(crate) fn evict_if_needed(&self, written: Option<&[u8]>) {
+        if !self.over_limits() {
+            return;
+        }
+        let Ok(_evicting) = self.evicting.try_lock() else {
+            return;
+        };
+        self.purge_expired();
+        if !self.over_limits() {
+            return;
+        }
+        let target_len = self.max_entries.map(|max| max - max / 16);
+        let target_bytes = self.max_bytes.map(|max| (max - max / 16) as u64);
+        let target_weight = self.max_weight.map(|max| max - max / 16);
+        let mut scored: Vec<((u64, u64), Vec<u8>)> = self
+            .inner
+            .iter()
+            .filter(|entry| written != Some(entry.key().as_slice()))
+            .map(|entry| (self.eviction_score(&entry), entry.key().clone()))
+            .collect();
+        scored.sort_unstable_by_key(|(score, _key)| *score);
+        for (_score, key) in scored {
+            let len_ok = target_len.is_none_or(|target| self.inner.len() <= target);
+            let bytes_ok = target_bytes.is_none_or(|target| self.approx_bytes() <= target);
+            let weight_ok = target_weight.is_none_or(|target| self.total_weight() <= target);
+            if len_ok && bytes_ok && weight_ok {
+                break;
+            }
+            self.evict_key(&key);
+        }
+    }
+
+    /// Entries with the lowest scores are evicted first
+    pub(crate) fn eviction_score(&self, entry: &Entry) -> (u64, u64) {
+        let recency = entry.last_access.load(Ordering::Relaxed);
+        match self.eviction_policy {
+            EvictionPolicy::Lru => (recency, 0),
+            EvictionPolicy::Lfu => (entry.hits.load(Ordering::Relaxed), recency),
+            EvictionPolicy::Weighted => (entry.credit.load(Ordering::Relaxed), recency),
+        }
+    }
+
+    pub(crate) fn evict_key(&self, key: &[u8]) {
+        if let Some((key, entry)) = self.inner.remove(key) {
+            self.stats.sub_entry(&key, &entry);
+            StatCounters::incr(&self.stats.evictions, 1);
+            self.inflation
+                .fetch_max(entry.credit.load(Ordering::Relaxed), Ordering::Relaxed);
+            self.detach(&key, &entry.tags);
+            self.notify_evicted(&key, &entry.value, EvictionReason::CapacityEvicted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn hooks_can_register_hooks() {
+        let cache: &'static DashmapCache = Box::leak(Box::new(DashmapCache::new()));
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        cache.on_evict(move |_key, _value, reason| {
+            assert_eq!(reason, EvictionReason::Removed);
+            cache.on_evict(|_key, _value, _reason| {});
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        });
+        cache.put(&vec![], &1, &"one").unwrap();
+        assert!(cache.remove(&1).unwrap());
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert!(cache.has_evict_hooks());
+    }
+
+    #[test]
+    fn full_lfu_cache_admits_new_keys() {
+        let cache = DashmapCache::builder()
+            .max_entries(4)
+            .eviction_policy(EvictionPolicy::Lfu)
+            .build();
+        let cached = |i: u32| cache.inner.contains_key(&rmp_serde::to_vec(&i).unwrap());
+        for i in 0..4 {
+            cache.cached(&vec![], |i: &u32| *i, i).unwrap();
+            cache.cached(&vec![], |i: &u32| *i, i).unwrap();
+        }
+        cache.cached(&vec![], |i: &u32| *i, 4).unwrap();
+        assert!(cached(4));
+        assert_eq!(cache.inner.len(), 4);
+        cache.cached(&vec![], |i: &u32| *i, 5).unwrap();
+        assert!(cached(5));
+    }
+}
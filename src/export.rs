@@ -0,0 +1,132 @@
+use serde_bytes::{ByteBuf, Bytes};
+use std::io::{Read, Write};
+use std::time::Instant;
+
+use crate::entry::Entry;
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Keys and stored values of the live entries tagged with tag, to be given to import()
+    /// Values are exported as stored, the importing cache must use the same Serializer, KeyStrategy
+    /// and compression setting
+    pub fn export_tag(&self, tag: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let now = Instant::now();
+        self.iter_tag(tag)
+            .filter_map(|key| {
+                let value = self
+                    .inner
+                    .get(&key)
+                    .filter(|entry| !entry.is_expired(now))?
+                    .value
+                    .clone();
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    /// Adds exported entries to the cache, tagged with invalidate_keys, the other entries being left as they are
+    /// An imported entry replaces any value of its key, and expires after the default ttl
+    pub fn import<I>(&self, entries: I, invalidate_keys: &Vec<String>)
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        for (key, value) in entries {
+            let _flight = self.lock_key_at(key.clone());
+            self.insert(
+                invalidate_keys,
+                &key,
+                Entry::new(value, self.default_expiry()),
+            );
+        }
+    }
+
+    /// export_tag() written to writer as MessagePack, a count followed by the entries
+    /// Returns the number of entries written
+    pub fn export_tag_to<W: Write>(&self, tag: &str, mut writer: W) -> Result<usize, CacheError> {
+        let entries = self.export_tag(tag);
+        rmp_serde::encode::write(&mut writer, &(entries.len() as u64))?;
+        for (key, value) in &entries {
+            rmp_serde::encode::write(&mut writer, &(Bytes::new(key), Bytes::new(value)))?;
+        }
+        writer.flush()?;
+        Ok(entries.len())
+    }
+
+    /// import() of the entries written by export_tag_to(), read one at a time from reader
+    /// Returns the number of entries imported, those read before a failure remain imported
+    pub fn import_from<R: Read>(
+        &self,
+        mut reader: R,
+        invalidate_keys: &Vec<String>,
+    ) -> Result<usize, CacheError> {
+        let count: u64 = rmp_serde::from_read(&mut reader)?;
+        for _ in 0..count {
+            let (key, value): (ByteBuf, ByteBuf) = rmp_serde::from_read(&mut reader)?;
+            self.import([(key.into_vec(), value.into_vec())], invalidate_keys);
+        }
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exported(count: u32) -> (DashmapCache, Vec<u8>) {
+        let cache = DashmapCache::new();
+        let users = vec!["users".to_owned()];
+        for i in 0..count {
+            cache.put(&users, &i, &format!("user {i}")).unwrap();
+        }
+        cache.put(&vec![], &"other", &0u8).unwrap();
+        let mut stream = Vec::new();
+        assert_eq!(
+            cache.export_tag_to("users", &mut stream).unwrap(),
+            count as usize
+        );
+        (cache, stream)
+    }
+
+    #[test]
+    fn streamed_entries_import_back() {
+        let (_cache, stream) = exported(5);
+        let imported = DashmapCache::new();
+        let tags = vec!["imported".to_owned()];
+        assert_eq!(imported.import_from(stream.as_slice(), &tags).unwrap(), 5);
+        assert_eq!(imported.len(), 5);
+        for i in 0..5u32 {
+            assert_eq!(imported.get(&i).unwrap(), Some(format!("user {i}")));
+        }
+        assert!(!imported.contains(&"other").unwrap());
+        assert_eq!(imported.tag_len("imported"), 5);
+        assert_eq!(imported.tag_len("users"), 0);
+
+        let (_cache, stream) = exported(0);
+        assert_eq!(
+            DashmapCache::new()
+                .import_from(stream.as_slice(), &tags)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn truncated_streams_fail_keeping_the_entries_read() {
+        let (cache, stream) = exported(5);
+        // Entries are exported in no particular order but all have the same length
+        let key = cache.key_of(&0u32).unwrap();
+        let value = cache.encode(&"user 0").unwrap();
+        let entry_len = rmp_serde::to_vec(&(Bytes::new(&key), Bytes::new(&value)))
+            .unwrap()
+            .len();
+        let cut = 1 + 2 * entry_len + entry_len / 2;
+        let imported = DashmapCache::new();
+        let res = imported.import_from(&stream[..cut], &vec![]);
+        assert!(matches!(res, Err(CacheError::Decode(_))));
+        assert_eq!(imported.len(), 2);
+        assert!(matches!(
+            DashmapCache::new().import_from(&[][..], &vec![]),
+            Err(CacheError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use core::future::Future;
+use core::slice;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::entry::{Entry, ExpiryOf};
+use crate::stats::StatCounters;
+use crate::{CacheError, CacheOutcome, DashmapCache, Serializer};
+
+/// What looking a key up before computing its value found
+enum Lookup<H> {
+    Hit(H, CacheOutcome),
+    /// Nothing live is cached for the key, expired telling whether an expired value was in its place
+    Miss {
+        expired: bool,
+    },
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Reads the live value stored for key through read, asking the backend too if backend is set
+    /// Callers only set it once they hold the key lock, so that a miss costs a single backend round trip
+    fn read_at<H>(
+        &self,
+        key: &[u8],
+        invalidate_keys: &[String],
+        backend: bool,
+        read: &mut impl FnMut(&[u8]) -> Result<H, CacheError>,
+    ) -> Result<Lookup<H>, CacheError> {
+        let now = Instant::now();
+        let expired = self
+            .inner
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now));
+        let hit = match self.live_entry(key) {
+            Some(entry) => {
+                StatCounters::incr(&self.stats.hits, 1);
+                #[cfg(feature = "tracing")]
+                tracing::trace!(key = crate::instrument::key_hash(key), "hit");
+                let age = now.saturating_duration_since(entry.created_at);
+                Some((read(&entry.value)?, age))
+            }
+            None if backend => match self.fetch_backend(key) {
+                Some(stored) => Some((read(&stored)?, Duration::ZERO)),
+                None => None,
+            },
+            None => None,
+        };
+        Ok(match hit {
+            Some((hit, age)) => {
+                self.record_tag_hit(invalidate_keys);
+                Lookup::Hit(hit, CacheOutcome::hit(age))
+            }
+            None => Lookup::Miss { expired },
+        })
+    }
+
+    /// Single-flight core of the methods caching the value computed for one key
+    /// Hits are read through read; concurrent misses queue on the key lock and the first one runs
+    /// compute, store turning its value into what the caller gets and the entry to cache, if any
+    pub(crate) fn single_flight<V, H, E>(
+        &self,
+        key: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        mut read: impl FnMut(&[u8]) -> Result<H, CacheError>,
+        compute: impl FnOnce() -> Result<V, E>,
+        store: impl FnOnce(V) -> Result<(H, Option<Entry>), CacheError>,
+    ) -> Result<(H, CacheOutcome), E>
+    where
+        E: From<CacheError>,
+    {
+        let expired = match self.read_at(&key, invalidate_keys, false, &mut read)? {
+            Lookup::Hit(hit, outcome) => return Ok((hit, outcome)),
+            Lookup::Miss { expired } => expired,
+        };
+        let _flight = self.lock_key_at(key.clone());
+        let expired = match self.read_at(&key, invalidate_keys, true, &mut read)? {
+            Lookup::Hit(hit, outcome) => return Ok((hit, outcome)),
+            Lookup::Miss { expired: meanwhile } => expired || meanwhile,
+        };
+        self.fill_miss(&key, invalidate_keys, expired, compute, store)
+    }
+
+    /// Async version of single_flight(), compute returning the future to await on a miss
+    pub(crate) async fn async_single_flight<Fut, V, H, E>(
+        &self,
+        key: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        mut read: impl FnMut(&[u8]) -> Result<H, CacheError>,
+        compute: impl FnOnce() -> Fut,
+        store: impl FnOnce(V) -> Result<(H, Option<Entry>), CacheError>,
+    ) -> Result<(H, CacheOutcome), E>
+    where
+        Fut: Future<Output = Result<V, E>>,
+        E: From<CacheError>,
+    {
+        let expired = match self.read_at(&key, invalidate_keys, false, &mut read)? {
+            Lookup::Hit(hit, outcome) => return Ok((hit, outcome)),
+            Lookup::Miss { expired } => expired,
+        };
+        let _flight = self.lock_key_at_async(key.clone()).await;
+        let expired = match self.read_at(&key, invalidate_keys, true, &mut read)? {
+            Lookup::Hit(hit, outcome) => return Ok((hit, outcome)),
+            Lookup::Miss { expired: meanwhile } => expired || meanwhile,
+        };
+        if let Some(err) = self.cached_panic(&key) {
+            return Err(err.into());
+        }
+        StatCounters::incr(&self.stats.misses, 1);
+        let epochs = self.epochs_of(invalidate_keys);
+        let start = Instant::now();
+        let val = self
+            .compute_future(slice::from_ref(&key), invalidate_keys, compute)
+            .await?;
+        let outcome = CacheOutcome::computed(expired, start.elapsed());
+        let (output, entry) = store(val)?;
+        if let Some(entry) = entry {
+            self.store(invalidate_keys, &key, entry, &epochs);
+        }
+        Ok((output, outcome))
+    }
+
+    /// Awaits the future compute returns for keys the way misses are computed: instrumented,
+    /// dropped with CacheError::Timeout past the builder timeout and guarded by the panic policy
+    /// compute is called right away, so that the returned future doesn't hold it
+    pub(crate) fn compute_future<'a, Fut, V, E>(
+        &'a self,
+        keys: &'a [Vec<u8>],
+        tags: &'a [String],
+        compute: impl FnOnce() -> Fut,
+    ) -> impl Future<Output = Result<V, E>> + 'a
+    where
+        Fut: Future<Output = Result<V, E>> + 'a,
+        E: From<CacheError>,
+    {
+        let fut = self.guard_panic(keys, compute);
+        async move {
+            let fut = self.guard_panic_async(keys, self.timed(fut?));
+            self.compute_async(keys, tags, fut).await?
+        }
+    }
+
+    /// Computes and caches the value of key, which the caller holds the lock of, see single_flight()
+    pub(crate) fn fill_miss<V, H, E>(
+        &self,
+        key: &[u8],
+        invalidate_keys: &Vec<String>,
+        expired: bool,
+        compute: impl FnOnce() -> Result<V, E>,
+        store: impl FnOnce(V) -> Result<(H, Option<Entry>), CacheError>,
+    ) -> Result<(H, CacheOutcome), E>
+    where
+        E: From<CacheError>,
+    {
+        if let Some(err) = self.cached_panic(key) {
+            return Err(err.into());
+        }
+        StatCounters::incr(&self.stats.misses, 1);
+        let epochs = self.epochs_of(invalidate_keys);
+        let start = Instant::now();
+        let keys = slice::from_ref(&key);
+        let val = self.compute(keys, invalidate_keys, || self.guard_panic(keys, compute))??;
+        let outcome = CacheOutcome::computed(expired, start.elapsed());
+        let (output, entry) = store(val)?;
+        if let Some(entry) = entry {
+            self.store(invalidate_keys, key, entry, &epochs);
+        }
+        Ok((output, outcome))
+    }
+
+    /// store argument of single_flight() caching the serialized value with expiry
+    /// Nothing is cached when encoding fails with return_value_on_cache_error set
+    pub(crate) fn value_store<'a, V: Serialize>(
+        &'a self,
+        expiry: impl ExpiryOf<V> + 'a,
+    ) -> impl FnOnce(V) -> Result<(V, Option<Entry>), CacheError> + 'a {
+        move |val| {
+            let entry = self
+                .encode_value(&val)?
+                .map(|val_bytes| Entry::new(val_bytes, expiry.expiry_of(&val)));
+            Ok((val, entry))
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use core::future::Future;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::time::Duration;
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// What the cache did to answer a cached_with_info() call
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CacheStatus {
+    /// The value was read from the cache
+    Hit,
+    /// Nothing was cached for the key, the value was computed
+    Miss,
+    /// The value cached for the key had expired, the value was computed again
+    Refreshed,
+}
+
+/// Written as HIT, MISS or REFRESHED, as in an X-Cache header
+impl std::fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Refreshed => "REFRESHED",
+        })
+    }
+}
+
+/// How a value returned by cached_with_info() was obtained
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheOutcome {
+    pub status: CacheStatus,
+    /// Time the closure took to compute the value, None on a hit
+    pub load_duration: Option<Duration>,
+    /// Time since the value was cached, as in an Age header
+    /// Zero for a computed value, and for a value read from the backend
+    pub age: Duration,
+}
+
+impl CacheOutcome {
+    pub fn is_hit(&self) -> bool {
+        self.status == CacheStatus::Hit
+    }
+
+    pub(crate) fn hit(age: Duration) -> Self {
+        Self {
+            status: CacheStatus::Hit,
+            load_duration: None,
+            age,
+        }
+    }
+
+    pub(crate) fn computed(expired: bool, load_duration: Duration) -> Self {
+        Self {
+            status: if expired {
+                CacheStatus::Refreshed
+            } else {
+                CacheStatus::Miss
+            },
+            load_duration: Some(load_duration),
+            age: Duration::ZERO,
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Same as cached(), along with whether the value was a hit, how long computing it took and its age
+    pub fn cached_with_info<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<(V, CacheOutcome), CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.single_flight(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |stored| self.decode(stored),
+            || Ok(closure(&arg)),
+            self.value_store(self.default_expiry()),
+        )
+    }
+
+    /// Async version of cached_with_info()
+    pub async fn async_cached_with_info<F, Fut, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<(V, CacheOutcome), CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.async_single_flight(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |stored| self.decode(stored),
+            || async { Ok(closure(arg).await) },
+            self.value_store(self.default_expiry()),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn reports_miss_then_hit_then_refresh() {
+        let cache = DashmapCache::builder()
+            .default_ttl(Duration::from_millis(50))
+            .build();
+        let (val, outcome) = cache.cached_with_info(&vec![], |x: &u32| x * 2, 2).unwrap();
+        assert_eq!(val, 4);
+        assert_eq!(outcome.status, CacheStatus::Miss);
+        assert!(outcome.load_duration.is_some());
+        std::thread::sleep(Duration::from_millis(10));
+        let (val, outcome) = cache.cached_with_info(&vec![], |x: &u32| x * 3, 2).unwrap();
+        assert_eq!(val, 4);
+        assert!(outcome.is_hit());
+        assert_eq!(outcome.load_duration, None);
+        assert!(outcome.age >= Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(50));
+        let (val, outcome) = cache.cached_with_info(&vec![], |x: &u32| x * 3, 2).unwrap();
+        assert_eq!(val, 6);
+        assert_eq!(outcome.status, CacheStatus::Refreshed);
+        assert_eq!(outcome.status.to_string(), "REFRESHED");
+    }
+
+    #[tokio::test]
+    async fn async_version_reports_the_same() {
+        let cache = DashmapCache::new();
+        let (val, outcome) = cache
+            .async_cached_with_info(&vec![], |x: u32| async move { x + 1 }, 1)
+            .await
+            .unwrap();
+        assert_eq!((val, outcome.status), (2, CacheStatus::Miss));
+        let (val, outcome) = cache
+            .async_cached_with_info(&vec![], |x: u32| async move { x + 2 }, 1)
+            .await
+            .unwrap();
+        assert_eq!((val, outcome.status), (2, CacheStatus::Hit));
+    }
+}
@@ -0,0 +1,119 @@
+use serde::de::DeserializeOwned;
+use std::time::Instant;
+
+use crate::key::decode_key;
+use crate::{DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Number of entries, including expired ones that were not purged yet
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Number of entries tagged with tag, including expired ones that were not purged yet
+    pub fn tag_len(&self, tag: &str) -> usize {
+        self.apply_remote_invalidations();
+        self.tags.get(tag).map_or(0, |keys| keys.len())
+    }
+
+    /// Keys of the live entries as they are stored, in no particular order
+    /// The map is not locked as a whole, entries written meanwhile may or may not be listed
+    pub fn keys(&self) -> Vec<Vec<u8>> {
+        self.apply_remote_invalidations();
+        let now = Instant::now();
+        self.inner
+            .iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Args of the live entries that were cached for an A, decoded from their keys
+    /// None under a digest KeyStrategy, only KeyStrategy::Full keys being decodable
+    /// Args cached through a TypedCache are listed by TypedCache::keys() instead
+    pub fn keys_as<A: DeserializeOwned>(&self) -> Option<Vec<A>> {
+        if !self.keys_decodable() {
+            return None;
+        }
+        Some(
+            self.keys()
+                .iter()
+                .filter_map(|key| decode_key(key))
+                .collect(),
+        )
+    }
+
+    /// Keys of the live entries tagged with tag, as they are stored
+    pub fn iter_tag(&self, tag: &str) -> impl Iterator<Item = Vec<u8>> {
+        self.apply_remote_invalidations();
+        let mut keys: Vec<Vec<u8>> = self
+            .tags
+            .get(tag)
+            .map(|keys| keys.iter().map(|key| key.clone()).collect())
+            .unwrap_or_default();
+        let now = Instant::now();
+        keys.retain(|key| {
+            self.inner
+                .get(key)
+                .is_some_and(|entry| !entry.is_expired(now))
+        });
+        keys.into_iter()
+    }
+
+    /// iter_tag() decoding the keys the way keys_as() does, None under a digest KeyStrategy
+    pub fn iter_tag_as<A: DeserializeOwned>(
+        &self,
+        tag: &str,
+    ) -> Option<impl Iterator<Item = A> + '_> {
+        if !self.keys_decodable() {
+            return None;
+        }
+        Some(self.iter_tag(tag).filter_map(|key| decode_key(&key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_are_decoded_back_from_full_keys() {
+        let cache = DashmapCache::new();
+        let tags = vec!["small".to_owned()];
+        cache.put(&tags, &1u32, &10u32).unwrap();
+        cache.put(&vec![], &2u32, &20u32).unwrap();
+        let squares = cache.register_type::<u32, u64>("squares").unwrap();
+        squares.cached(&vec![], |x| u64::from(x * x), 3).unwrap();
+        let mut args = cache.keys_as::<u32>().unwrap();
+        args.sort_unstable();
+        assert_eq!(args, [1, 2]);
+        assert_eq!(
+            cache
+                .iter_tag_as::<u32>("small")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            [1]
+        );
+        assert_eq!(squares.keys(), Some(vec![3]));
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn digested_keys_are_not_decoded() {
+        let cache = DashmapCache::builder()
+            .key_strategy(crate::KeyStrategy::Xxhash)
+            .build();
+        let tags = vec!["small".to_owned()];
+        cache.put(&tags, &1u32, &10u32).unwrap();
+        let squares = cache.register_type::<u32, u64>("squares").unwrap();
+        squares.cached(&vec![], |x| u64::from(x * x), 3).unwrap();
+        assert_eq!(cache.keys().len(), 2);
+        assert!(cache.keys_as::<u32>().is_none());
+        assert!(cache.iter_tag_as::<u32>("small").is_none());
+        assert_eq!(squares.keys(), None);
+    }
+}
@@ -0,0 +1,188 @@
+use core::future::Future;
+use core::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+use crate::{DashmapCache, Serializer};
+
+/// Label identifying the cache in the metrics it reports, see DashmapCacheBuilder::metrics_name()
+pub(crate) type CacheName = Arc<str>;
+
+pub(crate) fn default_name() -> CacheName {
+    Arc::from("default")
+}
+
+/// Stats counter, also reported as a metrics counter with the metrics feature
+#[derive(Debug)]
+pub(crate) struct Counter {
+    value: AtomicU64,
+    #[cfg(feature = "metrics")]
+    metric: &'static str,
+    #[cfg(feature = "metrics")]
+    cache: CacheName,
+}
+
+impl Counter {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn new(metric: &'static str, cache: &CacheName, value: u64) -> Self {
+        Self {
+            value: AtomicU64::new(value),
+            #[cfg(feature = "metrics")]
+            metric,
+            #[cfg(feature = "metrics")]
+            cache: cache.clone(),
+        }
+    }
+
+    pub(crate) fn incr(&self, by: u64) {
+        self.value.fetch_add(by, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!(self.metric, "cache" => self.cache.clone()).increment(by);
+    }
+
+    /// Same counter reported under cache
+    pub(crate) fn copy_for(&self, cache: &CacheName) -> Self {
+        #[cfg(feature = "metrics")]
+        let metric = self.metric;
+        #[cfg(not(feature = "metrics"))]
+        let metric = "";
+        Self::new(metric, cache, self.value.load(Ordering::Relaxed))
+    }
+}
+
+impl Deref for Counter {
+    type Target = AtomicU64;
+
+    fn deref(&self) -> &AtomicU64 {
+        &self.value
+    }
+}
+
+/// Reports the number of entries and their bytes as metrics gauges
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_contents(cache: &CacheName, entries: f64, bytes: f64) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::gauge!("dashmap_cache_entries", "cache" => cache.clone()).increment(entries);
+        metrics::gauge!("dashmap_cache_bytes", "cache" => cache.clone()).increment(bytes);
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn clear_contents(cache: &CacheName) {
+    metrics::gauge!("dashmap_cache_entries", "cache" => cache.clone()).set(0.0);
+    metrics::gauge!("dashmap_cache_bytes", "cache" => cache.clone()).set(0.0);
+}
+
+/// Computation of a miss being timed and traced, see DashmapCache::compute()
+struct Load {
+    #[cfg(feature = "metrics")]
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+/// Digest of key identifying it in traces
+#[cfg(feature = "tracing")]
+pub(crate) fn key_hash(key: &[u8]) -> u64 {
+    use std::hash::{DefaultHasher, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write(key);
+    hasher.finish()
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Runs compute, the computation of the misses of keys tagged with tags, timing and tracing it
+    pub(crate) fn compute<K: AsRef<[u8]>, R>(
+        &self,
+        keys: &[K],
+        tags: &[String],
+        compute: impl FnOnce() -> R,
+    ) -> R {
+        let load = self.start_load(keys, tags);
+        #[cfg(feature = "tracing")]
+        let val = load.span.in_scope(compute);
+        #[cfg(not(feature = "tracing"))]
+        let val = compute();
+        self.finish_load(load, tags);
+        val
+    }
+
+    /// Async version of compute(), the span being entered each time fut is polled
+    pub(crate) async fn compute_async<K: AsRef<[u8]>, F: Future>(
+        &self,
+        keys: &[K],
+        tags: &[String],
+        fut: F,
+    ) -> F::Output {
+        let load = self.start_load(keys, tags);
+        #[cfg(feature = "tracing")]
+        let val = tracing::Instrument::instrument(fut, load.span.clone()).await;
+        #[cfg(not(feature = "tracing"))]
+        let val = fut.await;
+        self.finish_load(load, tags);
+        val
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn start_load<K: AsRef<[u8]>>(&self, keys: &[K], tags: &[String]) -> Load {
+        Load {
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: {
+                let span = tracing::debug_span!(
+                    "compute",
+                    key = tracing::field::Empty,
+                    keys = keys.len(),
+                    tags = ?tags
+                );
+                if let [key] = keys {
+                    span.record("key", key_hash(key.as_ref()));
+                }
+                span
+            },
+        }
+    }
+
+    /// Reports the time a miss of a call tagged with tags took to compute
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn finish_load(&self, load: Load, tags: &[String]) {
+        #[cfg(feature = "metrics")]
+        {
+            let seconds = load.start.elapsed().as_secs_f64();
+            let cache = &self.stats.name;
+            metrics::histogram!("dashmap_cache_load_duration_seconds", "cache" => cache.clone())
+                .record(seconds);
+            if self.metrics_tag_labels {
+                for tag in tags {
+                    metrics::histogram!(
+                        "dashmap_cache_tag_load_duration_seconds",
+                        "cache" => cache.clone(),
+                        "tag" => tag.clone()
+                    )
+                    .record(seconds);
+                }
+            }
+        }
+    }
+
+    /// Counts a hit of a call tagged with tags under each tag, when tag labels are enabled
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn record_tag_hit(&self, tags: &[String]) {
+        #[cfg(feature = "metrics")]
+        if self.metrics_tag_labels {
+            for tag in tags {
+                metrics::counter!(
+                    "dashmap_cache_tag_hits_total",
+                    "cache" => self.stats.name.clone(),
+                    "tag" => tag.clone()
+                )
+                .increment(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use core::fmt::Debug;
+#[cfg(any(feature = "tokio", feature = "redis"))]
+use std::collections::hash_map::RandomState;
+#[cfg(any(feature = "tokio", feature = "redis"))]
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// What a transport delivers to the caches subscribed to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RemoteInvalidation {
+    /// Another instance invalidated this tag
+    Tag(String),
+    /// Some invalidations could not be delivered, the subscribed cache then drops all its local
+    /// entries as it can no longer tell which are stale
+    Lost,
+}
+
+/// Called with each invalidation of another instance, returns false once it no longer wants any
+pub type InvalidationHandler = Box<dyn Fn(RemoteInvalidation) -> bool + Send + Sync>;
+
+/// Delivery started by InvalidationTransport::subscribe(), stopped once dropped
+pub struct Subscription {
+    cancel: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl Subscription {
+    /// Subscription running cancel when dropped, which should stop the delivery without waiting
+    /// for another message
+    pub fn new(cancel: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            cancel: Some(Box::new(cancel)),
+        }
+    }
+
+    /// Subscription with nothing to stop, delivery ending once the handler returns false
+    pub fn detached() -> Self {
+        Self { cancel: None }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel();
+        }
+    }
+}
+
+impl Debug for Subscription {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("cancellable", &self.cancel.is_some())
+            .finish()
+    }
+}
+
+/// Channel through which caches of several instances tell each other about invalidated tags
+/// See DashmapCacheBuilder::invalidation_transport()
+pub trait InvalidationTransport: Send + Sync + Debug {
+    /// Announces to the other instances that tag was invalidated
+    fn publish(&self, tag: &str) -> Result<(), CacheError>;
+
+    /// Starts delivering the invalidations published by other instances to on_tag, until it
+    /// returns false or the subscription is dropped
+    /// Tags published through this same transport should not be delivered back, and messages
+    /// that may have been missed should be reported as RemoteInvalidation::Lost
+    fn subscribe(&self, on_tag: InvalidationHandler) -> Result<Subscription, CacheError>;
+
+    /// Transport on the same channel acting as a separate instance, used by the clones of a cache
+    /// so that the cache and its clones get each other's invalidations
+    /// None, the default, has clones share this transport, which then doesn't deliver the
+    /// invalidations of a clone to the cache it was cloned from nor the other way around
+    fn fork(&self) -> Option<Arc<dyn InvalidationTransport>> {
+        None
+    }
+}
+
+/// Random id telling the messages of a transport instance apart from the others
+#[cfg(any(feature = "tokio", feature = "redis"))]
+pub(crate) fn origin_id() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Tags received from the transport, applied by the cache before its next read
+#[derive(Debug, Default)]
+pub(crate) struct PendingInvalidations {
+    tags: Mutex<Vec<String>>,
+    /// Set when the transport reported lost invalidations
+    lost: AtomicBool,
+    dirty: AtomicBool,
+    /// Stops the delivery thread of the transport along with the cache
+    subscription: Mutex<Option<Subscription>>,
+}
+
+impl PendingInvalidations {
+    pub(crate) fn subscribe(
+        transport: Option<&dyn InvalidationTransport>,
+    ) -> (Arc<Self>, Result<(), CacheError>) {
+        let pending = Arc::new(Self::default());
+        let Some(transport) = transport else {
+            return (pending, Ok(()));
+        };
+        let queue: Weak<Self> = Arc::downgrade(&pending);
+        let subscribed = transport.subscribe(Box::new(move |invalidation| {
+            let Some(queue) = queue.upgrade() else {
+                return false;
+            };
+            match invalidation {
+                RemoteInvalidation::Tag(tag) => queue
+                    .tags
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(tag),
+                RemoteInvalidation::Lost => queue.lost.store(true, Ordering::Release),
+            }
+            queue.dirty.store(true, Ordering::Release);
+            true
+        }));
+        let subscribed = subscribed.map(|subscription| {
+            *pending
+                .subscription
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = Some(subscription);
+        });
+        (pending, subscribed)
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Invalidates locally the tags other instances published since the last call
+    pub(crate) fn apply_remote_invalidations(&self) {
+        if !self
+            .remote_invalidations
+            .dirty
+            .swap(false, Ordering::Acquire)
+        {
+            return;
+        }
+        if self
+            .remote_invalidations
+            .lost
+            .swap(false, Ordering::Acquire)
+        {
+            self.remote_invalidations
+                .tags
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
+            self.clear();
+            return;
+        }
+        let tags = std::mem::take(
+            &mut *self
+                .remote_invalidations
+                .tags
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+        for tag in tags.iter().flat_map(|tag| self.with_dependents(tag)) {
+            self.invalidate_local(&tag);
+        }
+    }
+
+    pub(crate) fn publish_invalidation(&self, tag: &str) {
+        if let Some(transport) = &self.transport {
+            if let Err(err) = transport.publish(tag) {
+                self.record_error(err);
+            }
+        }
+    }
+}
+
+/// InvalidationTransport between caches of the same process, over a tokio broadcast channel
+/// Clones share the channel and each act as a separate instance, give one to every cache
+/// Delivery runs on a thread per subscribed cache, no runtime is needed, and stops along with the cache
+/// A cache falling behind the channel capacity drops all its entries instead of missing invalidations
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct BroadcastTransport {
+    /// Tags along with the origin that published them, None waking the subscribers being stopped
+    sender: tokio::sync::broadcast::Sender<(u64, Option<String>)>,
+    origin: u64,
+}
+
+#[cfg(feature = "tokio")]
+impl BroadcastTransport {
+    /// capacity is the number of tags a slow subscriber can fall behind by before missing some
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: tokio::sync::broadcast::channel(capacity).0,
+            origin: origin_id(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Clone for BroadcastTransport {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            origin: origin_id(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl InvalidationTransport for BroadcastTransport {
+    fn publish(&self, tag: &str) -> Result<(), CacheError> {
+        // Sending fails only when nobody is subscribed, which leaves nobody to tell
+        let _ = self.sender.send((self.origin, Some(tag.to_owned())));
+        Ok(())
+    }
+
+    fn subscribe(&self, on_tag: InvalidationHandler) -> Result<Subscription, CacheError> {
+        use tokio::sync::broadcast::error::RecvError;
+
+        let mut receiver = self.sender.subscribe();
+        let origin = self.origin;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        std::thread::Builder::new()
+            .name("dashmap-cache-invalidations".to_owned())
+            .spawn(move || loop {
+                let received = receiver.blocking_recv();
+                if stop.load(Ordering::Acquire) {
+                    break;
+                }
+                let invalidation = match received {
+                    Ok((_from, None)) => continue,
+                    Ok((from, Some(_tag))) if from == origin => continue,
+                    Ok((_from, Some(tag))) => RemoteInvalidation::Tag(tag),
+                    Err(RecvError::Lagged(_)) => RemoteInvalidation::Lost,
+                    Err(RecvError::Closed) => break,
+                };
+                if !on_tag(invalidation) {
+                    break;
+                }
+            })?;
+        let sender = self.sender.clone();
+        Ok(Subscription::new(move || {
+            stopped.store(true, Ordering::Release);
+            let _ = sender.send((origin, None));
+        }))
+    }
+
+    fn fork(&self) -> Option<Arc<dyn InvalidationTransport>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::DashmapCacheBuilder;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(mut done: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if done() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn lagging_cache_drops_its_entries() {
+        let transport = BroadcastTransport::new(1);
+        let local = DashmapCacheBuilder::new()
+            .invalidation_transport(transport.clone())
+            .build();
+        let remote = transport.clone();
+        for i in 0..50 {
+            local.put(&vec![format!("user:{i}")], &i, &i).unwrap();
+        }
+        for i in 0..50 {
+            remote.publish(&format!("user:{i}")).unwrap();
+        }
+        assert!(wait_until(|| (0..50).all(|i| !local.contains(&i).unwrap())));
+    }
+
+    #[test]
+    fn dropped_caches_stop_their_delivery_thread() {
+        let transport = BroadcastTransport::new(16);
+        let cache = DashmapCacheBuilder::new()
+            .invalidation_transport(transport.clone())
+            .build();
+        let clones: Vec<_> = (0..4).map(|_| cache.clone()).collect();
+        assert_eq!(transport.sender.receiver_count(), 5);
+        drop(clones);
+        drop(cache);
+        assert!(wait_until(|| transport.sender.receiver_count() == 0));
+    }
+
+    #[test]
+    fn clones_get_each_other_s_invalidations() {
+        let cache = DashmapCacheBuilder::new()
+            .invalidation_transport(BroadcastTransport::new(16))
+            .build();
+        let clone = cache.clone();
+        let tags = vec!["users".to_owned()];
+        cache.put(&tags, &1u8, &1u8).unwrap();
+        clone.put(&tags, &2u8, &2u8).unwrap();
+        clone.invalidate("users");
+        assert!(wait_until(|| !cache.contains(&1u8).unwrap()));
+        cache.put(&tags, &1u8, &1u8).unwrap();
+        clone.put(&tags, &2u8, &2u8).unwrap();
+        cache.invalidate("users");
+        assert!(wait_until(|| !clone.contains(&2u8).unwrap()));
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use crate::{DashmapCache, Serializer};
+
+/// Background cleanup task started by DashmapCache::start_janitor()
+/// Dropping the handle leaves the task running, call stop() to end it
+#[derive(Debug)]
+pub struct JanitorHandle {
+    task: JoinHandle<()>,
+}
+
+impl JanitorHandle {
+    /// Ends the task, a pass in progress being cut short
+    pub fn stop(self) {
+        self.task.abort();
+    }
+
+    /// Whether the task is still running, it ends by itself once the cache is dropped
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl<S: Serializer + Send + Sync + 'static> DashmapCache<S> {
+    /// Starts a tokio task removing expired entries and compacting the tag index every interval,
+    /// reclaiming the memory of keys that are not read anymore
+    /// With enforce_capacity, each pass also evicts down to max_entries, max_bytes, max_weight and
+    /// the tag max_entries, which the cache otherwise only enforces when it is written to
+    /// Must be called within a tokio runtime, the task runs until stopped or the cache is dropped
+    pub fn start_janitor(
+        self: &Arc<Self>,
+        interval: Duration,
+        enforce_capacity: bool,
+    ) -> JanitorHandle {
+        let cache = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes right away
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                cache.purge_expired();
+                cache.compact_tags();
+                if enforce_capacity {
+                    cache.evict_if_needed(None);
+                    let tags: Vec<String> = cache
+                        .tag_policies
+                        .iter()
+                        .map(|policy| policy.key().clone())
+                        .collect();
+                    cache.evict_tags_if_needed(&tags, None);
+                }
+            }
+        });
+        JanitorHandle { task }
+    }
+
+    /// Drops the keys tag sets still reference after their entry left, and the tag sets left empty
+    fn compact_tags(&self) {
+        self.tags.retain(|_tag, keys| {
+            keys.retain(|key| self.inner.contains_key(key));
+            !keys.is_empty()
+        });
+    }
+}
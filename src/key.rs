@@ -0,0 +1,60 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// How the serialized argument of a call is turned into the key it is cached under
+/// Digests can't be turned back into args, DashmapCache::keys_as() and TypedCache::keys() return
+/// None under them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyStrategy {
+    /// The serialized argument itself, exact but as large as the argument
+    #[default]
+    Full,
+    /// 128 bit XXH3 digest of the serialized argument
+    #[cfg(feature = "xxhash")]
+    Xxhash,
+    /// 256 bit BLAKE3 digest of the serialized argument, slower than Xxhash but collision resistant
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl KeyStrategy {
+    /// None when bytes are used as they are
+    #[cfg_attr(
+        not(any(feature = "xxhash", feature = "blake3")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn digest(self, bytes: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Full => None,
+            #[cfg(feature = "xxhash")]
+            Self::Xxhash => Some(xxhash_rust::xxh3::xxh3_128(bytes).to_le_bytes().to_vec()),
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => Some(blake3::hash(bytes).as_bytes().to_vec()),
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Whether args can be decoded back from the keys, see decode_key()
+    pub(crate) fn keys_decodable(&self) -> bool {
+        self.key_strategy == KeyStrategy::Full
+    }
+
+    /// Key under which the value computed for arg is cached
+    pub(crate) fn key_of<A: Serialize>(&self, arg: &A) -> Result<Vec<u8>, CacheError> {
+        Ok(self.digest_key(rmp_serde::to_vec(arg)?))
+    }
+
+    pub(crate) fn digest_key(&self, bytes: Vec<u8>) -> Vec<u8> {
+        self.key_strategy.digest(&bytes).unwrap_or(bytes)
+    }
+}
+
+/// Reverse of key_of() for KeyStrategy::Full, None unless key is exactly one encoded A
+pub(crate) fn decode_key<A: DeserializeOwned>(key: &[u8]) -> Option<A> {
+    let mut rest = key;
+    let arg = A::deserialize(&mut rmp_serde::Deserializer::new(&mut rest)).ok()?;
+    rest.is_empty().then_some(arg)
+}
@@ -1,20 +1,310 @@
 use core::future::Future;
 use core::hash::Hash;
+use core::slice;
+use dashmap::mapref::entry::Entry as MapEntry;
+use dashmap::mapref::one::Ref;
 use dashmap::{DashMap, DashSet};
 use serde::{Deserialize, Serialize};
+use std::any::TypeId;
 use std::fmt::Debug;
 use std::marker::{Send, Sync};
-use std::pin::Pin;
-#[derive(Clone, Debug)]
-pub struct DashmapCache {
-    inner: DashMap<Vec<u8>, Vec<u8>>,
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "rkyv")]
+mod archived;
+mod backend;
+mod batch;
+mod builder;
+mod by_key;
+mod cache_entry;
+mod cancel;
+mod compression;
+mod config;
+mod deadline;
+mod dependency;
+mod duration;
+mod entry;
+mod epoch;
+mod eviction;
+mod export;
+mod flight;
+mod info;
+mod inspect;
+mod instrument;
+mod invalidation;
+#[cfg(feature = "tokio")]
+mod janitor;
+mod key;
+mod lock;
+mod native;
+mod negative;
+mod panic;
+mod pattern;
+mod raw;
+#[cfg(feature = "redis")]
+mod redis_backend;
+#[cfg(feature = "tokio")]
+mod refresh;
+mod scope;
+mod serializer;
+mod snapshot;
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "tokio")]
+mod swr;
+mod tag_policy;
+mod typed;
+mod version;
+mod warm;
+mod wrap;
+
+pub use backend::{BackendEntry, CacheBackend};
+pub use builder::{BuildError, DashmapCacheBuilder, RefreshTagPolicy};
+pub use cache_entry::{CacheEntry, EntryValue};
+pub use compression::Compression;
+pub use config::CacheConfig;
+#[cfg(feature = "macros")]
+pub use dashmap_cache_macros::dashmap_cached;
+pub use eviction::{EvictionPolicy, EvictionReason};
+pub use info::{CacheOutcome, CacheStatus};
+#[cfg(feature = "tokio")]
+pub use invalidation::BroadcastTransport;
+pub use invalidation::{
+    InvalidationHandler, InvalidationTransport, RemoteInvalidation, Subscription,
+};
+#[cfg(feature = "tokio")]
+pub use janitor::JanitorHandle;
+pub use key::KeyStrategy;
+pub use lock::KeyGuard;
+pub use native::NativeCache;
+pub use negative::NegativeOutcome;
+pub use panic::PanicPolicy;
+#[cfg(feature = "redis")]
+pub use redis_backend::{RedisBackend, RedisTransport};
+pub use scope::Scope;
+#[cfg(feature = "bincode")]
+pub use serializer::Bincode;
+#[cfg(feature = "json")]
+pub use serializer::Json;
+#[cfg(feature = "postcard")]
+pub use serializer::Postcard;
+pub use serializer::{MsgPack, Serializer};
+pub use stats::CacheStats;
+#[cfg(feature = "stream")]
+pub use stream::CachedStream;
+pub use tag_policy::TagPolicy;
+pub use typed::TypedCache;
+pub use wrap::{AsyncCachedFn, CachedFn};
+
+use entry::{Entry, Expiry, ExpiryOf};
+use epoch::TagEpochs;
+use eviction::EvictionHooks;
+use invalidation::PendingInvalidations;
+use lock::KeyLock;
+use stats::StatCounters;
+
+#[derive(Debug)]
+pub struct DashmapCache<S = MsgPack> {
+    inner: DashMap<Vec<u8>, Entry>,
     tags: DashMap<String, DashSet<Vec<u8>>>,
+    namespaces: DashMap<String, (TypeId, TypeId)>,
+    tag_epochs: TagEpochs,
+    tag_policies: DashMap<String, TagPolicy>,
+    /// Tags invalidated along with each tag, see add_tag_dependency()
+    tag_dependents: DashMap<String, DashSet<String>>,
+    generation: AtomicU64,
+    locks: DashMap<Vec<u8>, Arc<KeyLock>>,
+    /// Shards of the maps written on every miss, see DashmapCacheBuilder::shard_amount()
+    shard_amount: Option<usize>,
+    last_error: Mutex<Option<CacheError>>,
+    next_sweep: Mutex<Instant>,
+    sweep_interval: Duration,
+    access_clock: AtomicU64,
+    evicting: Mutex<()>,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    max_weight: Option<u64>,
+    /// Credit of the last entry evicted by weight, see EvictionPolicy::Weighted
+    inflation: AtomicU64,
+    default_ttl: Option<Duration>,
+    /// Percentage by which ttls are randomly shortened or lengthened, see DashmapCacheBuilder::ttl_jitter()
+    ttl_jitter: u8,
+    panic_policy: PanicPolicy,
+    /// Panics cached by PanicPolicy::CacheFor, by key
+    panics: DashMap<Vec<u8>, panic::CachedPanic>,
+    #[cfg(feature = "tokio")]
+    timeout: Option<Duration>,
+    #[cfg(feature = "metrics")]
+    metrics_tag_labels: bool,
+    key_strategy: KeyStrategy,
+    compression: Compression,
+    compression_threshold: usize,
+    backend: Option<Arc<dyn CacheBackend>>,
+    transport: Option<Arc<dyn InvalidationTransport>>,
+    remote_invalidations: Arc<PendingInvalidations>,
+    eviction_policy: EvictionPolicy,
+    evict_hooks: EvictionHooks,
+    stats: StatCounters,
+    #[cfg(feature = "tokio")]
+    revalidating: swr::Revalidating,
+    refresh_tag_policy: RefreshTagPolicy,
+    return_value_on_cache_error: bool,
+    serializer: S,
+}
+
+/// Clones the cached contents and settings, key locks and the last error of the original are not carried over
+/// The clone joins the invalidation transport as another instance, see InvalidationTransport::fork()
+impl<S: Serializer + Clone> Clone for DashmapCache<S> {
+    fn clone(&self) -> Self {
+        let transport = self
+            .transport
+            .as_ref()
+            .map(|transport| transport.fork().unwrap_or_else(|| transport.clone()));
+        let (remote_invalidations, subscribed) =
+            PendingInvalidations::subscribe(transport.as_deref());
+        let cache = Self {
+            inner: self.inner.clone(),
+            tags: self.tags.clone(),
+            namespaces: self.namespaces.clone(),
+            tag_epochs: self.tag_epochs.clone(),
+            tag_policies: self.tag_policies.clone(),
+            tag_dependents: self.tag_dependents.clone(),
+            generation: AtomicU64::new(self.generation.load(Ordering::Relaxed)),
+            locks: match self.shard_amount {
+                Some(shard_amount) => DashMap::with_shard_amount(shard_amount),
+                None => DashMap::new(),
+            },
+            shard_amount: self.shard_amount,
+            last_error: Mutex::new(None),
+            next_sweep: Mutex::new(*self.next_sweep.lock().unwrap_or_else(|e| e.into_inner())),
+            sweep_interval: self.sweep_interval,
+            access_clock: AtomicU64::new(self.tick()),
+            evicting: Mutex::new(()),
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            max_weight: self.max_weight,
+            inflation: AtomicU64::new(self.inflation.load(Ordering::Relaxed)),
+            default_ttl: self.default_ttl,
+            ttl_jitter: self.ttl_jitter,
+            panic_policy: self.panic_policy,
+            panics: self.panics.clone(),
+            #[cfg(feature = "tokio")]
+            timeout: self.timeout,
+            #[cfg(feature = "metrics")]
+            metrics_tag_labels: self.metrics_tag_labels,
+            key_strategy: self.key_strategy,
+            compression: self.compression,
+            compression_threshold: self.compression_threshold,
+            backend: self.backend.clone(),
+            transport,
+            remote_invalidations,
+            eviction_policy: self.eviction_policy,
+            evict_hooks: self.evict_hooks.clone(),
+            stats: self.stats.clone(),
+            #[cfg(feature = "tokio")]
+            revalidating: DashSet::new(),
+            refresh_tag_policy: self.refresh_tag_policy,
+            return_value_on_cache_error: self.return_value_on_cache_error,
+            serializer: self.serializer.clone(),
+        };
+        if let Err(err) = subscribed {
+            cache.record_error(err);
+        }
+        cache
+    }
 }
 
+/// New variants may be added, some of them only with a feature enabled
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CacheError {
     Decode(rmp_serde::decode::Error),
     Encode(rmp_serde::encode::Error),
+    /// The namespace is already registered for another type pair
+    NamespaceConflict(String),
+    /// Error raised by a Serializer other than MsgPack
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+    Io(std::io::Error),
+    /// The file given to restore() is not a snapshot this version can read
+    Snapshot(String),
+    /// A cached_many() closure returned another number of values than it was given args
+    BatchLength {
+        expected: usize,
+        returned: usize,
+    },
+    /// Failure reported by a CacheBackend
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+    /// The task spawned by a tokio_cached() closure panicked or was cancelled, nothing was cached
+    #[cfg(feature = "tokio")]
+    Join(tokio::task::JoinError),
+    /// An async computation outlasted the builder timeout and was dropped, nothing was cached
+    Timeout,
+    /// A lock guarding shared state was poisoned by a thread panicking while holding it
+    Poisoned,
+    /// The computation of a miss panicked with this message, see PanicPolicy
+    Panicked(String),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decode(e) => write!(f, "failed to decode a cached value: {e}"),
+            Self::Encode(e) => write!(f, "failed to encode a value: {e}"),
+            Self::NamespaceConflict(namespace) => write!(
+                f,
+                "namespace {namespace:?} is already registered for other types"
+            ),
+            Self::Codec(e) => write!(f, "serializer error: {e}"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Snapshot(reason) => write!(f, "unreadable snapshot: {reason}"),
+            Self::BatchLength { expected, returned } => write!(
+                f,
+                "batch closure returned {returned} values for {expected} args"
+            ),
+            Self::Backend(e) => write!(f, "cache backend error: {e}"),
+            #[cfg(feature = "tokio")]
+            Self::Join(e) => write!(f, "computation task failed: {e}"),
+            Self::Timeout => f.write_str("computation timed out"),
+            Self::Poisoned => f.write_str("a cache lock was poisoned"),
+            Self::Panicked(message) => write!(f, "computation panicked: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(e) => Some(e),
+            Self::Encode(e) => Some(e),
+            Self::Codec(e) | Self::Backend(e) => Some(&**e),
+            Self::Io(e) => Some(e),
+            #[cfg(feature = "tokio")]
+            Self::Join(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for CacheError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        Self::Poisoned
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for CacheError {
+    fn from(value: tokio::task::JoinError) -> Self {
+        Self::Join(value)
+    }
+}
+
+impl From<std::io::Error> for CacheError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
 }
 
 impl From<rmp_serde::decode::Error> for CacheError {
@@ -29,32 +319,362 @@ impl From<rmp_serde::encode::Error> for CacheError {
     }
 }
 
-impl<'a> DashmapCache {
+impl Default for DashmapCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DashmapCache {
     pub fn new() -> Self {
-        let inner = DashMap::new();
-        Self {
+        Self::builder().build()
+    }
+
+    /// Cache holding at most max_entries, least recently used entries being evicted first
+    /// Panics if max_entries is 0
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self::builder().max_entries(max_entries).build()
+    }
+
+    /// Cache whose maps are split into shard_amount shards, a power of two greater than 1
+    /// More shards than DashMap's default cut lock contention under write-heavy loads on many cores
+    pub fn with_shard_amount(shard_amount: usize) -> Result<Self, BuildError> {
+        Self::builder().shard_amount(shard_amount).try_build()
+    }
+
+    /// Cache holding capacity entries, and as many tags, before reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::builder()
+            .initial_capacity(capacity)
+            .initial_tag_capacity(capacity)
+            .build()
+    }
+
+    pub fn builder() -> DashmapCacheBuilder {
+        DashmapCacheBuilder::new()
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Cache storing its values with serializer instead of MessagePack
+    pub fn with_serializer(serializer: S) -> Self {
+        DashmapCache::builder().serializer(serializer).build()
+    }
+
+    pub(crate) fn from_builder(builder: DashmapCacheBuilder<S>) -> Self {
+        let inner = builder.new_map(builder.initial_capacity);
+        let tags = builder.new_map(builder.initial_tag_capacity);
+        let locks = builder.new_map(0);
+        let (remote_invalidations, subscribed) =
+            PendingInvalidations::subscribe(builder.transport.as_deref());
+        let cache = Self {
             inner,
-            tags: DashMap::new(),
+            tags,
+            namespaces: DashMap::new(),
+            tag_epochs: TagEpochs::default(),
+            tag_policies: builder.tag_policies.into_iter().collect(),
+            tag_dependents: DashMap::new(),
+            generation: AtomicU64::new(0),
+            locks,
+            shard_amount: builder.shard_amount,
+            last_error: Mutex::new(None),
+            next_sweep: Mutex::new(Instant::now() + builder.sweep_interval),
+            sweep_interval: builder.sweep_interval,
+            access_clock: AtomicU64::new(0),
+            evicting: Mutex::new(()),
+            max_entries: builder.max_entries,
+            max_bytes: builder.max_bytes,
+            max_weight: builder.max_weight,
+            inflation: AtomicU64::new(0),
+            default_ttl: builder.default_ttl,
+            ttl_jitter: builder.ttl_jitter,
+            panic_policy: builder.panic_policy,
+            panics: DashMap::new(),
+            #[cfg(feature = "tokio")]
+            timeout: builder.timeout,
+            #[cfg(feature = "metrics")]
+            metrics_tag_labels: builder.metrics_tag_labels,
+            key_strategy: builder.key_strategy,
+            compression: builder.compression,
+            compression_threshold: builder.compression_threshold,
+            backend: builder.backend,
+            transport: builder.transport,
+            remote_invalidations,
+            eviction_policy: builder.eviction_policy,
+            evict_hooks: EvictionHooks::default(),
+            #[cfg(feature = "metrics")]
+            stats: StatCounters::named(builder.metrics_name),
+            #[cfg(not(feature = "metrics"))]
+            stats: StatCounters::default(),
+            #[cfg(feature = "tokio")]
+            revalidating: DashSet::new(),
+            refresh_tag_policy: builder.refresh_tag_policy,
+            return_value_on_cache_error: builder.return_value_on_cache_error,
+            serializer: builder.serializer,
+        };
+        if let Err(err) = subscribed {
+            cache.record_error(err);
         }
+        cache
+    }
+
+    /// Expiry of values cached without an explicit ttl
+    pub(crate) fn default_expiry(&self) -> Expiry {
+        Expiry::from_ttl(self.default_ttl)
+    }
+
+    fn insert(&self, tags: &Vec<String>, key: &[u8], entry: Entry) -> Option<Entry> {
+        self.insert_tagged(tags, key, entry, RefreshTagPolicy::Merge)
     }
 
-    fn insert(&self, tags: &Vec<String>, key: Vec<u8>, val: Vec<u8>) -> Option<Vec<u8>> {
+    /// Stores entry under key, policy deciding what becomes of the tags of a replaced entry
+    fn insert_tagged(
+        &self,
+        tags: &Vec<String>,
+        key: &[u8],
+        entry: Entry,
+        policy: RefreshTagPolicy,
+    ) -> Option<Entry> {
+        let previous = self.insert_local(tags, key, entry, policy);
+        self.write_through(key);
+        previous
+    }
+
+    /// insert_tagged() without writing through to the backend
+    fn insert_local(
+        &self,
+        tags: &Vec<String>,
+        key: &[u8],
+        mut entry: Entry,
+        policy: RefreshTagPolicy,
+    ) -> Option<Entry> {
+        *entry.last_access.get_mut() = self.tick();
+        *entry.credit.get_mut() = self.credit_of(&entry);
+        self.stats.add_entry(key, &entry);
+        StatCounters::incr(&self.stats.insertions, 1);
+        entry.tags = tags.clone();
+        let previous = match self.inner.entry(key.to_vec()) {
+            MapEntry::Occupied(mut occupied) => {
+                let previous_tags = &occupied.get().tags;
+                match policy {
+                    RefreshTagPolicy::Replace => {}
+                    RefreshTagPolicy::Preserve => entry.tags = previous_tags.clone(),
+                    RefreshTagPolicy::Merge => {
+                        for tag in previous_tags {
+                            if !entry.tags.contains(tag) {
+                                entry.tags.push(tag.clone());
+                            }
+                        }
+                    }
+                }
+                self.jitter_ttl(&mut entry);
+                self.cap_tag_ttl(&mut entry);
+                Some(occupied.insert(entry))
+            }
+            MapEntry::Vacant(vacant) => {
+                self.jitter_ttl(&mut entry);
+                self.cap_tag_ttl(&mut entry);
+                vacant.insert(entry);
+                None
+            }
+        };
+        if policy != RefreshTagPolicy::Preserve || previous.is_none() {
+            for tag in tags {
+                self.tags
+                    .entry(tag.to_owned())
+                    .or_default()
+                    .insert(key.to_vec());
+            }
+        }
+        if let Some(previous) = &previous {
+            self.stats.sub_entry(key, previous);
+            if policy == RefreshTagPolicy::Replace {
+                let dropped: Vec<String> = previous
+                    .tags
+                    .iter()
+                    .filter(|tag| !tags.contains(tag))
+                    .cloned()
+                    .collect();
+                self.detach(key, &dropped);
+            }
+            self.notify_evicted(key, &previous.value, EvictionReason::Replaced);
+        }
+        self.sweep_if_due();
+        self.evict_if_needed(Some(key));
+        self.evict_tags_if_needed(tags, Some(key));
+        previous
+    }
+
+    /// Removes key from the tag index entries of tags, dropping tags left without keys
+    pub(crate) fn detach(&self, key: &[u8], tags: &[String]) {
         for tag in tags {
-            if !self.tags.contains_key(tag) {
-                let dash = DashSet::new();
-                dash.insert(key.clone());
-                self.tags.insert(tag.to_owned(), dash);
+            if let Some(keys) = self.tags.get(tag) {
+                keys.remove(key);
+            }
+            self.tags.remove_if(tag, |_tag, keys| keys.is_empty());
+        }
+    }
+
+    /// Returns the entry stored for key unless it has expired, in which case it is removed
+    fn live_entry(&self, key: &[u8]) -> Option<Ref<'_, Vec<u8>, Entry>> {
+        self.apply_remote_invalidations();
+        let entry = self.inner.get(key)?;
+        if !entry.is_expired(Instant::now()) {
+            self.touch(&entry);
+            return Some(entry);
+        }
+        drop(entry);
+        if let Some((key, entry)) = self
+            .inner
+            .remove_if(key, |_key, entry| entry.is_expired(Instant::now()))
+        {
+            self.stats.sub_entry(&key, &entry);
+            StatCounters::incr(&self.stats.expirations, 1);
+            self.detach(&key, &entry.tags);
+            self.notify_evicted(&key, &entry.value, EvictionReason::Expired);
+        }
+        None
+    }
+
+    /// Decodes the live value cached for key, if any
+    fn lookup<V: for<'b> Deserialize<'b>>(&self, key: &[u8]) -> Result<Option<V>, CacheError> {
+        Ok(self.lookup_entry(key)?.map(|(val, _stale)| val))
+    }
+
+    /// lookup() also telling whether the value is past its ttl and only served stale
+    fn lookup_entry<V: for<'b> Deserialize<'b>>(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<(V, bool)>, CacheError> {
+        match self.live_entry(key) {
+            None => Ok(self.lookup_backend(key)?.map(|val| (val, false))),
+            Some(entry) => {
+                StatCounters::incr(&self.stats.hits, 1);
+                #[cfg(feature = "tracing")]
+                tracing::trace!(key = instrument::key_hash(key), "hit");
+                let stale = entry.is_stale(Instant::now());
+                Ok(Some((self.decode::<V>(&entry.value)?, stale)))
+            }
+        }
+    }
+
+    /// Caches a freshly computed value, unless encoding it failed with return_value_on_cache_error set
+    /// or one of its tags was invalidated since epochs were taken
+    fn fill<V: Serialize>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        key: &[u8],
+        val: &V,
+        expiry: Expiry,
+        epochs: &[u64],
+    ) -> Result<(), CacheError> {
+        if let Some(val_bytes) = self.encode_value(val)? {
+            self.store(invalidate_keys, key, Entry::new(val_bytes, expiry), epochs);
+        }
+        Ok(())
+    }
+
+    /// Inserts entry unless one of its tags was invalidated since epochs were taken
+    fn store(&self, invalidate_keys: &Vec<String>, key: &[u8], entry: Entry, epochs: &[u64]) {
+        self.insert(invalidate_keys, key, entry);
+        self.discard_if_invalidated(invalidate_keys, key, epochs);
+    }
+
+    /// Removes every expired entry, returns how many were dropped
+    /// Expired entries are also removed lazily when read, and by a sweep run on writes every sweep_interval
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.inner.retain(|key, entry| {
+            if entry.is_expired(now) {
+                self.stats.sub_entry(key, entry);
+                expired.push((
+                    key.clone(),
+                    std::mem::take(&mut entry.tags),
+                    std::mem::take(&mut entry.value),
+                ));
+                false
             } else {
-                self.tags.alter(tag, |_k, ex_tags| {
-                    ex_tags.insert(key.clone());
-                    ex_tags
-                })
+                true
+            }
+        });
+        for (key, tags, value) in &expired {
+            self.detach(key, tags);
+            self.notify_evicted(key, value, EvictionReason::Expired);
+        }
+        StatCounters::incr(&self.stats.expirations, expired.len() as u64);
+        expired.len()
+    }
+
+    fn sweep_if_due(&self) {
+        let now = Instant::now();
+        let due = match self.next_sweep.try_lock() {
+            Ok(mut next_sweep) if *next_sweep <= now => {
+                *next_sweep = now + self.sweep_interval;
+                true
+            }
+            _ => false,
+        };
+        if due {
+            self.purge_expired();
+        }
+    }
+
+    /// Invalidation generation of each tag, bumped by every invalidate() call, see TagEpochs
+    /// preceded by the generation of the whole cache, bumped by clear()
+    fn epochs_of(&self, tags: &[String]) -> Vec<u64> {
+        std::iter::once(self.generation.load(Ordering::Relaxed))
+            .chain(tags.iter().map(|tag| self.tag_epochs.get(tag)))
+            .collect()
+    }
+
+    /// Drops a freshly stored entry if one of its tags got invalidated while its value was computed
+    /// The entry is written before checking, so an invalidation racing with the write either
+    /// bumps the epoch before the check or finds the entry in its tag set afterwards
+    fn discard_if_invalidated(&self, tags: &[String], key: &[u8], epochs: &[u64]) {
+        if self.epochs_of(tags) != epochs {
+            if let Some((key, entry)) = self.inner.remove(key) {
+                self.stats.sub_entry(&key, &entry);
+                StatCounters::incr(&self.stats.invalidations, 1);
+                self.detach(&key, &entry.tags);
+                self.notify_evicted(&key, &entry.value, EvictionReason::Invalidated);
             }
+            self.detach(key, tags);
+            self.with_backend(|backend| backend.remove(key));
         }
-        self.inner.insert(key, val)
     }
 
-    /// Atomic operation to replace a cached entry by a new computation value
+    /// Serializes a computed value for storage
+    /// With return_value_on_cache_error set, a failure is kept as the last error and Ok(None) tells the caller to skip caching
+    fn encode_value<V: Serialize>(&self, val: &V) -> Result<Option<Vec<u8>>, CacheError> {
+        match self.encode(val) {
+            Ok(val_bytes) => Ok(Some(val_bytes)),
+            Err(err) if self.return_value_on_cache_error => {
+                self.record_error(err);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub(crate) fn record_error(&self, err: CacheError) {
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(err);
+    }
+
+    /// Takes the last error swallowed because of return_value_on_cache_error, or raised by a background refresh
+    pub fn take_last_error(&self) -> Option<CacheError> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Replaces the entry for arg by a new computation value, tags already attached to it being
+    /// handled according to the cache RefreshTagPolicy
+    /// The key lock is held from the computation to the write, as for cached() misses: cached() calls
+    /// missing the key meanwhile wait for the refreshed value, and a fill in progress completes before
+    /// the refresh starts, so it can't overwrite the refreshed value. Hits keep reading the previous
+    /// value until the refreshed one is written
     pub fn refresh_cache<F, A, V>(
         &self,
         invalidate_keys: &Vec<String>,
@@ -66,17 +686,90 @@ impl<'a> DashmapCache {
         A: Hash + Sync + Send + Eq + Serialize,
         V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
     {
-        let arg_bytes = rmp_serde::to_vec(&arg)?;
-        let val = closure(&arg);
-        let val_bytes = rmp_serde::to_vec(&val)?;
-        self.insert(invalidate_keys, arg_bytes, val_bytes);
+        self.refresh_cache_at(self.key_of(&arg)?, invalidate_keys, closure, arg)
+    }
+
+    pub(crate) fn refresh_cache_at<F, A, V>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        V: Serialize,
+    {
+        let _flight = self.lock_key_at(arg_bytes.clone());
+        let epochs = self.epochs_of(invalidate_keys);
+        let keys = slice::from_ref(&arg_bytes);
+        let val = self.compute(keys, invalidate_keys, || {
+            self.guard_panic(keys, || closure(&arg))
+        })?;
+        let val_bytes = self.encode(&val)?;
+        self.store_refreshed(invalidate_keys, &arg_bytes, val_bytes, &epochs);
+        self.forget_panic(&arg_bytes);
         Ok(val)
     }
 
+    /// refresh_cache() over a batch of args, each key being locked while it is refreshed
+    /// Keys and values are serialized into two buffers reused across the batch, so each stored
+    /// byte vector is allocated once at its exact size instead of growing while encoding
+    pub fn refresh_many<F, A, V, I>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        args: I,
+    ) -> Result<Vec<V>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+        I: IntoIterator<Item = A>,
+    {
+        let mut arg_buf = Vec::new();
+        let mut val_buf = Vec::new();
+        args.into_iter()
+            .map(|arg| {
+                arg_buf.clear();
+                rmp_serde::encode::write(&mut arg_buf, &arg)?;
+                let digest = self.key_strategy.digest(&arg_buf);
+                let key = digest.as_deref().unwrap_or(&arg_buf);
+                let _flight = self.lock_key_at(key.to_vec());
+                let epochs = self.epochs_of(invalidate_keys);
+                let keys = slice::from_ref(&key);
+                let val = self.compute(keys, invalidate_keys, || {
+                    self.guard_panic(keys, || closure(&arg))
+                })?;
+                val_buf.clear();
+                self.encode_into(&val, &mut val_buf)?;
+                let val_bytes = self
+                    .compressed(&val_buf)?
+                    .unwrap_or_else(|| val_buf.clone());
+                self.store_refreshed(invalidate_keys, key, val_bytes, &epochs);
+                self.forget_panic(key);
+                Ok(val)
+            })
+            .collect()
+    }
+
+    /// Writes a refreshed value, handling tags of existing keys according to the RefreshTagPolicy
+    pub(crate) fn store_refreshed(
+        &self,
+        invalidate_keys: &Vec<String>,
+        key: &[u8],
+        val_bytes: Vec<u8>,
+        epochs: &[u64],
+    ) {
+        let entry = Entry::new(val_bytes, self.default_expiry());
+        self.insert_tagged(invalidate_keys, key, entry, self.refresh_tag_policy);
+        self.discard_if_invalidated(invalidate_keys, key, epochs);
+    }
+
     /// Computes a signature for arg
     /// If already present in the cache, returns directly associated return value
     /// Otherwise, compute a new return value and fills the cache with it
-    /// It is recommended to use a call enum and dispatch in the same closure for the same cache if the input types or values are susceptible to overlap.
+    /// If several functions share the cache and their input types or values may overlap, go through typed() or register_type() handles
     pub fn cached<F, A, V>(
         &self,
         invalidate_keys: &Vec<String>,
@@ -88,51 +781,260 @@ impl<'a> DashmapCache {
         A: Hash + Sync + Send + Eq + Serialize,
         V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
     {
-        let arg_bytes = rmp_serde::to_vec(&arg)?;
+        self.cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+    }
 
-        match self.inner.get(&arg_bytes) {
-            None => {
-                let val = closure(&arg);
-                let val_bytes = rmp_serde::to_vec(&val)?;
-                self.insert(invalidate_keys, arg_bytes, val_bytes);
-                Ok(val)
-            }
-            Some(val) => {
-                let ret_val = rmp_serde::from_slice::<V>(&val)?;
-                Ok(ret_val.to_owned())
-            }
-        }
+    pub(crate) fn cached_at<F, A, V>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        expiry: Expiry,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        V: Serialize + for<'b> Deserialize<'b>,
+    {
+        self.try_cached_at(
+            arg_bytes,
+            invalidate_keys,
+            expiry,
+            |arg| Ok(closure(arg)),
+            arg,
+        )
+    }
+
+    pub(crate) fn try_cached_at<F, A, V, E>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        expiry: impl ExpiryOf<V>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: Fn(&A) -> Result<V, E>,
+        V: Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        // Concurrent misses queue on the key lock, the first one computes and the others find its value
+        let (val, _outcome) = self.single_flight(
+            arg_bytes,
+            invalidate_keys,
+            |stored| self.decode(stored),
+            || closure(&arg),
+            self.value_store(expiry),
+        )?;
+        Ok(val)
+    }
+
+    /// Same as cached() for a fallible closure
+    /// Errors are handed back to the caller and nothing is cached for them
+    pub fn try_cached<F, A, V, E>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: Fn(&A) -> Result<V, E>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        let arg_bytes = self.key_of(&arg)?;
+        self.try_cached_at(
+            arg_bytes,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+    }
+
+    /// Same as cached(), the value being dropped from the cache once ttl has elapsed
+    pub fn cached_with_ttl<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            Expiry::after(ttl),
+            closure,
+            arg,
+        )
+    }
+
+    /// Same as cached(), the entry weighing weight(&value) against max_weight instead of 1
+    /// Under EvictionPolicy::Weighted heavier entries are kept longer, weigh values by the time they
+    /// take to compute to keep the expensive ones
+    pub fn cached_weighted<W, F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        weight: W,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        W: Fn(&V) -> u64,
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let store = self.value_store(self.default_expiry());
+        let (val, _outcome) = self.single_flight(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |stored| self.decode(stored),
+            || Ok::<_, CacheError>(closure(&arg)),
+            |val| {
+                let weight = weight(&val);
+                let (val, entry) = store(val)?;
+                Ok((val, entry.map(|entry| entry.with_weight(weight))))
+            },
+        )?;
+        Ok(val)
     }
 
     /// Async version of cached()
-    pub async fn async_cached<F, A, V>(
+    /// The closure takes arg by value and returns any future, such as `|arg| async move { .. }`
+    pub async fn async_cached<F, Fut, A, V>(
         &self,
         invalidate_keys: &Vec<String>,
         closure: F,
         arg: A,
     ) -> Result<V, CacheError>
     where
-        F: Fn(&A) -> Pin<Box<dyn Future<Output = V>>>,
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
         A: Hash + Sync + Send + Eq + Serialize,
         V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
     {
-        let arg_bytes = rmp_serde::to_vec(&arg)?;
+        self.async_cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+        .await
+    }
 
-        match self.inner.get(&arg_bytes) {
-            None => {
-                let val = closure(&arg).await;
-                let val_bytes = rmp_serde::to_vec(&val)?;
-                self.insert(invalidate_keys, arg_bytes, val_bytes);
-                Ok(val)
-            }
-            Some(val) => {
-                let ret_val = rmp_serde::from_slice::<V>(&val)?;
-                Ok(ret_val.to_owned())
-            }
-        }
+    pub(crate) async fn async_cached_at<F, Fut, A, V>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        expiry: Expiry,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        V: Serialize + for<'b> Deserialize<'b>,
+    {
+        let closure = |arg: A| {
+            let fut = closure(arg);
+            async move { Ok(fut.await) }
+        };
+        self.try_async_cached_at(arg_bytes, invalidate_keys, expiry, closure, arg)
+            .await
+    }
+
+    pub(crate) async fn try_async_cached_at<F, Fut, A, V, E>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        expiry: Expiry,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        V: Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        let (val, _outcome) = self
+            .async_single_flight(
+                arg_bytes,
+                invalidate_keys,
+                |stored| self.decode(stored),
+                || closure(arg),
+                self.value_store(expiry),
+            )
+            .await?;
+        Ok(val)
+    }
+
+    /// Async version of try_cached()
+    pub async fn try_async_cached<F, Fut, A, V, E>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        let arg_bytes = self.key_of(&arg)?;
+        self.try_async_cached_at(
+            arg_bytes,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+        .await
+    }
+
+    /// Async version of cached_with_ttl()
+    pub async fn async_cached_with_ttl<F, Fut, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.async_cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            Expiry::after(ttl),
+            closure,
+            arg,
+        )
+        .await
     }
 
     /// Tokio version of cached()
+    /// If the task panics or is cancelled the call fails with CacheError::Join and nothing is cached, so the next call retries
+    /// Dropping the returned future, or reaching the builder timeout, aborts the task
     #[cfg(feature = "tokio")]
     pub async fn tokio_cached<F, A, V>(
         &self,
@@ -145,29 +1047,292 @@ impl<'a> DashmapCache {
         A: Hash + Sync + Send + Eq + Serialize,
         V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
     {
-        let arg_bytes = rmp_serde::to_vec(&arg)?;
+        self.tokio_cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+        .await
+    }
 
-        match self.inner.get(&arg_bytes) {
-            None => {
-                let val = closure(&arg).await.unwrap();
-                let val_bytes = rmp_serde::to_vec(&val)?;
-                self.insert(invalidate_keys, arg_bytes, val_bytes);
-                Ok(val)
-            }
-            Some(val) => {
-                let ret_val = rmp_serde::from_slice::<V>(&val)?;
-                Ok(ret_val.to_owned())
-            }
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn tokio_cached_at<F, A, V>(
+        &self,
+        arg_bytes: Vec<u8>,
+        invalidate_keys: &Vec<String>,
+        expiry: Expiry,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<V>,
+        V: Serialize + for<'b> Deserialize<'b>,
+    {
+        let closure = |arg: A| {
+            let handle = cancel::AbortOnDrop(closure(&arg));
+            async move { handle.await.map_err(CacheError::from) }
+        };
+        self.try_async_cached_at(arg_bytes, invalidate_keys, expiry, closure, arg)
+            .await
+    }
+
+    /// Tokio version of cached_with_ttl()
+    #[cfg(feature = "tokio")]
+    pub async fn tokio_cached_with_ttl<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.tokio_cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            Expiry::after(ttl),
+            closure,
+            arg,
+        )
+        .await
+    }
+
+    /// Tokio version of try_cached()
+    /// A task failing to join comes back as CacheError::Join converted into E, and like Err values is not cached
+    #[cfg(feature = "tokio")]
+    pub async fn try_tokio_cached<F, A, V, E>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<Result<V, E>>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        let arg_bytes = self.key_of(&arg)?;
+        let closure = |arg: A| {
+            let handle = cancel::AbortOnDrop(closure(&arg));
+            async move { handle.await.map_err(CacheError::from)? }
+        };
+        self.try_async_cached_at(
+            arg_bytes,
+            invalidate_keys,
+            self.default_expiry(),
+            closure,
+            arg,
+        )
+        .await
+    }
+
+    /// Removes the entry cached for arg, returns whether there was one
+    pub fn remove<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.remove_key(&self.key_of(arg)?))
+    }
+
+    /// Whether a live entry is cached for arg, without counting as an access
+    /// Only local entries are looked at, not the backend
+    pub fn contains<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.contains_key(&self.key_of(arg)?))
+    }
+
+    /// Value cached for arg if there is one, never computing anything
+    /// Counts as a hit or a miss like cached() does
+    pub fn get<A, V>(&self, arg: &A) -> Result<Option<V>, CacheError>
+    where
+        A: Serialize,
+        V: for<'b> Deserialize<'b>,
+    {
+        self.get_key(&self.key_of(arg)?)
+    }
+
+    /// Caches value for arg as if cached() had computed it, replacing any previous value
+    /// Waits for a fill or refresh of the key in progress, so that it can't overwrite value afterwards
+    /// Holding the KeyGuard of the key, use KeyGuard::fill() instead or this never returns
+    pub fn put<A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        arg: &A,
+        value: &V,
+    ) -> Result<(), CacheError>
+    where
+        A: Serialize,
+        V: Serialize,
+    {
+        self.put_key(invalidate_keys, &self.key_of(arg)?, value)
+    }
+
+    pub(crate) fn get_key<V: for<'b> Deserialize<'b>>(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<V>, CacheError> {
+        let val = self.lookup(key)?;
+        if val.is_none() {
+            StatCounters::incr(&self.stats.misses, 1);
         }
+        Ok(val)
+    }
+
+    pub(crate) fn put_key<V: Serialize>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        key: &[u8],
+        value: &V,
+    ) -> Result<(), CacheError> {
+        let val_bytes = self.encode(value)?;
+        let _flight = self.lock_key_at(key.to_vec());
+        self.insert(
+            invalidate_keys,
+            key,
+            Entry::new(val_bytes, self.default_expiry()),
+        );
+        Ok(())
+    }
+
+    pub(crate) fn remove_key(&self, key: &[u8]) -> bool {
+        self.panics.remove(key);
+        let local = self.inner.remove(key).map(|(key, entry)| {
+            self.stats.sub_entry(&key, &entry);
+            self.detach(&key, &entry.tags);
+            self.notify_evicted(&key, &entry.value, EvictionReason::Removed);
+        });
+        let remote = self.with_backend(|backend| backend.remove(key));
+        local.is_some() || remote == Some(true)
+    }
+
+    pub(crate) fn contains_key(&self, key: &[u8]) -> bool {
+        self.apply_remote_invalidations();
+        self.inner
+            .get(key)
+            .is_some_and(|entry| !entry.is_expired(Instant::now()))
     }
 
+    /// Removes every entry tagged with tag, along with its references from the other tags
+    /// Values still being computed for that tag when this is called are discarded instead of cached
+    /// The backend and the other instances listening on the invalidation transport are told too
+    /// Tags depending on tag, see add_tag_dependency(), are invalidated the same way
     pub fn invalidate(&self, tag: &str) {
-        let hashes = self.tags.get(tag);
-        if hashes.is_some() {
-            self.tags.remove(tag);
-            for hsh in hashes.unwrap().clone() {
-                self.inner.remove(&hsh);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("invalidate", tag).entered();
+        for tag in self.with_dependents(tag) {
+            self.invalidate_local(&tag);
+            self.with_backend(|backend| backend.invalidate_tag(&tag));
+            self.publish_invalidation(&tag);
+        }
+    }
+
+    pub(crate) fn invalidate_local(&self, tag: &str) {
+        self.tag_epochs.bump(tag);
+        if let Some((_tag, hashes)) = self.tags.remove(tag) {
+            for hsh in hashes {
+                if let Some((key, entry)) = self.inner.remove(&hsh) {
+                    self.stats.sub_entry(&key, &entry);
+                    StatCounters::incr(&self.stats.invalidations, 1);
+                    self.detach(&key, &entry.tags);
+                    self.notify_evicted(&key, &entry.value, EvictionReason::Invalidated);
+                }
             }
         }
     }
+
+    /// invalidate() for each of tags
+    pub fn invalidate_all(&self, tags: &[&str]) {
+        for tag in tags {
+            self.invalidate(tag);
+        }
+    }
+
+    /// Removes every entry and tag, values being computed meanwhile are discarded
+    /// The backend, if any, is left as it is
+    pub fn clear(&self) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("clear", entries = self.inner.len()).entered();
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        if self.has_evict_hooks() {
+            let keys: Vec<Vec<u8>> = self.inner.iter().map(|entry| entry.key().clone()).collect();
+            for key in keys {
+                if let Some((key, entry)) = self.inner.remove(&key) {
+                    self.stats.sub_entry(&key, &entry);
+                    StatCounters::incr(&self.stats.invalidations, 1);
+                    self.notify_evicted(&key, &entry.value, EvictionReason::Invalidated);
+                }
+            }
+        }
+        let removed = self.inner.len() as u64;
+        self.inner.clear();
+        self.tags.clear();
+        self.panics.clear();
+        self.stats.clear_contents();
+        StatCounters::incr(&self.stats.invalidations, removed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalidating_during_a_slow_computation_discards_its_value() {
+        let cache = DashmapCache::new();
+        let tags = vec!["t".to_owned()];
+        std::thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                cache.cached(
+                    &tags,
+                    |x: &u32| {
+                        std::thread::sleep(Duration::from_millis(100));
+                        x * 2
+                    },
+                    1,
+                )
+            });
+            std::thread::sleep(Duration::from_millis(30));
+            cache.invalidate("t");
+            assert_eq!(leader.join().unwrap().unwrap(), 2);
+        });
+        assert_eq!(cache.cached(&tags, |x: &u32| x * 3, 1).unwrap(), 3);
+    }
+
+    /// Value whose serialization always fails
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    struct Unencodable(u32);
+
+    impl Serialize for Unencodable {
+        fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("refusing to encode"))
+        }
+    }
+
+    #[test]
+    fn unencodable_values_are_returned_uncached() {
+        let cache = DashmapCache::builder()
+            .return_value_on_cache_error(true)
+            .build();
+        let val = cache.cached(&vec![], |x: &u32| Unencodable(*x), 1).unwrap();
+        assert_eq!(val, Unencodable(1));
+        assert!(matches!(
+            cache.take_last_error(),
+            Some(CacheError::Encode(_))
+        ));
+        assert!(cache.inner.is_empty());
+        assert!(cache.take_last_error().is_none());
+
+        let strict = DashmapCache::new();
+        let res = strict.cached(&vec![], |x: &u32| Unencodable(*x), 1);
+        assert!(matches!(res, Err(CacheError::Encode(_))));
+    }
+
+    #[test]
+    fn with_capacity_presizes_entries_and_tags() {
+        let cache = DashmapCache::with_capacity(1000);
+        assert!(cache.inner.capacity() >= 1000);
+        assert!(cache.tags.capacity() >= 1000);
+    }
 }
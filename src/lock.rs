@@ -0,0 +1,193 @@
+use core::future::Future;
+use core::task::{Context, Poll, Waker};
+use serde::Serialize;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+
+use dashmap::mapref::entry::Entry as MapEntry;
+
+use crate::entry::Entry;
+use crate::{CacheError, DashmapCache, MsgPack, Serializer};
+
+#[derive(Debug, Default)]
+struct KeyLockState {
+    released: bool,
+    wakers: Vec<Waker>,
+}
+
+/// Released once by the KeyGuard holding it, wakes both blocked threads and pending futures
+#[derive(Debug, Default)]
+pub(crate) struct KeyLock {
+    state: Mutex<KeyLockState>,
+    released: Condvar,
+}
+
+impl KeyLock {
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.released = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+        self.released.notify_all();
+    }
+
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while !state.released {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn wait_async(self: Arc<Self>) -> KeyLockWait {
+        KeyLockWait { lock: self }
+    }
+}
+
+struct KeyLockWait {
+    lock: Arc<KeyLock>,
+}
+
+impl Future for KeyLockWait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.lock.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.released {
+            Poll::Ready(())
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Exclusive claim on a cache key, obtained from DashmapCache::lock_key()
+/// Other lock_key() callers, and cached() calls missing the same key, wait until the guard is filled or dropped
+#[derive(Debug)]
+pub struct KeyGuard<'a, S: Serializer = MsgPack> {
+    cache: &'a DashmapCache<S>,
+    key: Vec<u8>,
+    lock: Arc<KeyLock>,
+}
+
+impl<S: Serializer> KeyGuard<'_, S> {
+    /// Caches val for the locked key and releases it
+    pub fn fill<V: Serialize>(self, val: &V) -> Result<(), CacheError> {
+        self.fill_tagged(&vec![], val)
+    }
+
+    /// Same as fill(), attaching the entry to the invalidate_keys tags
+    pub fn fill_tagged<V: Serialize>(
+        self,
+        invalidate_keys: &Vec<String>,
+        val: &V,
+    ) -> Result<(), CacheError> {
+        let val_bytes = self.cache.encode(val)?;
+        self.cache.insert(
+            invalidate_keys,
+            &self.key,
+            Entry::new(val_bytes, self.cache.default_expiry()),
+        );
+        Ok(())
+    }
+}
+
+impl<S: Serializer> Drop for KeyGuard<'_, S> {
+    fn drop(&mut self) {
+        self.cache.locks.remove(&self.key);
+        self.lock.release();
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Claims the key computed for arg, waiting for any other holder to release it first
+    /// The cache entry is not looked at: check it after locking if a concurrent fill matters
+    pub fn lock_key<A: Serialize>(&self, arg: &A) -> Result<KeyGuard<'_, S>, CacheError> {
+        Ok(self.lock_key_at(self.key_of(arg)?))
+    }
+
+    pub(crate) fn lock_key_at(&self, key: Vec<u8>) -> KeyGuard<'_, S> {
+        loop {
+            let held = match self.locks.entry(key.clone()) {
+                MapEntry::Vacant(vacant) => {
+                    let lock = Arc::new(KeyLock::default());
+                    vacant.insert(lock.clone());
+                    return KeyGuard {
+                        cache: self,
+                        key,
+                        lock,
+                    };
+                }
+                MapEntry::Occupied(occupied) => occupied.get().clone(),
+            };
+            held.wait();
+        }
+    }
+
+    /// lock_key_at() giving up instead of waiting when the key is already claimed
+    pub(crate) fn try_lock_key_at(&self, key: Vec<u8>) -> Option<KeyGuard<'_, S>> {
+        match self.locks.entry(key.clone()) {
+            MapEntry::Vacant(vacant) => {
+                let lock = Arc::new(KeyLock::default());
+                vacant.insert(lock.clone());
+                Some(KeyGuard {
+                    cache: self,
+                    key,
+                    lock,
+                })
+            }
+            MapEntry::Occupied(_) => None,
+        }
+    }
+
+    /// Async version of lock_key_at(), waiting without blocking the executor thread
+    pub(crate) async fn lock_key_at_async(&self, key: Vec<u8>) -> KeyGuard<'_, S> {
+        loop {
+            let held = match self.locks.entry(key.clone()) {
+                MapEntry::Vacant(vacant) => {
+                    let lock = Arc::new(KeyLock::default());
+                    vacant.insert(lock.clone());
+                    return KeyGuard {
+                        cache: self,
+                        key,
+                        lock,
+                    };
+                }
+                MapEntry::Occupied(occupied) => occupied.get().clone(),
+            };
+            held.wait_async().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn waiting_thread_gets_the_filled_value() {
+        let cache = DashmapCache::new();
+        let guard = cache.lock_key(&1u32).unwrap();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| cache.cached(&vec![], |x: &u32| x + 100, 1));
+            std::thread::sleep(Duration::from_millis(50));
+            guard.fill(&7u32).unwrap();
+            assert_eq!(waiter.join().unwrap().unwrap(), 7);
+        });
+    }
+
+    #[test]
+    fn dropping_the_guard_lets_the_waiter_compute() {
+        let cache = DashmapCache::new();
+        let guard = cache.lock_key(&1u32).unwrap();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| cache.cached(&vec![], |x: &u32| x + 100, 1));
+            std::thread::sleep(Duration::from_millis(50));
+            drop(guard);
+            assert_eq!(waiter.join().unwrap().unwrap(), 101);
+        });
+        assert!(cache.lock_key(&1u32).is_ok());
+    }
+}
@@ -0,0 +1,312 @@
+use core::future::Future;
+use dashmap::{DashMap, DashSet};
+use serde::Serialize;
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::epoch::TagEpochs;
+use crate::stats::StatCounters;
+use crate::{CacheError, CacheStats};
+
+/// Value stored by a NativeCache, shared with the callers instead of being serialized
+#[derive(Debug)]
+struct NativeEntry {
+    value: Arc<dyn Any + Send + Sync>,
+    expires_at: Option<Instant>,
+    tags: Vec<String>,
+}
+
+impl NativeEntry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// In-memory cache keeping values as they are behind an Arc, hits cost a lookup and an Arc clone
+/// Args are still serialized into keys and tags behave as in DashmapCache, but values need neither
+/// Serialize nor Clone, and nothing is written to a backend or a snapshot
+/// A key holds one value whatever its type: looking it up as another type is a miss, and caching
+/// it replaces the previous value, so functions sharing a NativeCache need args that can't collide
+#[derive(Debug, Default)]
+pub struct NativeCache {
+    inner: DashMap<Vec<u8>, NativeEntry>,
+    tags: DashMap<String, DashSet<Vec<u8>>>,
+    tag_epochs: TagEpochs,
+    generation: AtomicU64,
+    stats: StatCounters,
+}
+
+impl NativeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key_of<A: Serialize>(arg: &A) -> Result<Vec<u8>, CacheError> {
+        Ok(rmp_serde::to_vec(arg)?)
+    }
+
+    fn lookup<V: Send + Sync + 'static>(&self, key: &[u8]) -> Option<Arc<V>> {
+        let entry = self.inner.get(key)?;
+        if entry.is_expired(Instant::now()) {
+            drop(entry);
+            self.remove_expired(key);
+            return None;
+        }
+        let val = entry.value.clone().downcast::<V>().ok()?;
+        StatCounters::incr(&self.stats.hits, 1);
+        Some(val)
+    }
+
+    /// Returns whether key was expired and got removed
+    fn remove_expired(&self, key: &[u8]) -> bool {
+        let Some((key, entry)) = self
+            .inner
+            .remove_if(key, |_key, entry| entry.is_expired(Instant::now()))
+        else {
+            return false;
+        };
+        StatCounters::incr(&self.stats.expirations, 1);
+        self.detach(&key, &entry.tags);
+        true
+    }
+
+    /// Same as DashmapCache::epochs_of()
+    fn epochs_of(&self, tags: &[String]) -> Vec<u64> {
+        std::iter::once(self.generation.load(Ordering::Relaxed))
+            .chain(tags.iter().map(|tag| self.tag_epochs.get(tag)))
+            .collect()
+    }
+
+    fn insert<V: Send + Sync + 'static>(
+        &self,
+        tags: &[String],
+        key: Vec<u8>,
+        val: Arc<V>,
+        ttl: Option<Duration>,
+    ) {
+        StatCounters::incr(&self.stats.insertions, 1);
+        let entry = NativeEntry {
+            value: val,
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+            tags: tags.to_vec(),
+        };
+        for tag in tags {
+            self.tags
+                .entry(tag.to_owned())
+                .or_default()
+                .insert(key.clone());
+        }
+        if let Some(previous) = self.inner.insert(key.clone(), entry) {
+            let dropped: Vec<String> = previous
+                .tags
+                .into_iter()
+                .filter(|tag| !tags.contains(tag))
+                .collect();
+            self.detach(&key, &dropped);
+        }
+    }
+
+    /// Inserts the value unless one of its tags was invalidated since epochs were taken
+    fn store<V: Send + Sync + 'static>(
+        &self,
+        tags: &[String],
+        key: Vec<u8>,
+        val: Arc<V>,
+        ttl: Option<Duration>,
+        epochs: &[u64],
+    ) {
+        self.insert(tags, key.clone(), val, ttl);
+        if self.epochs_of(tags) != epochs {
+            if let Some((key, entry)) = self.inner.remove(&key) {
+                StatCounters::incr(&self.stats.invalidations, 1);
+                self.detach(&key, &entry.tags);
+            }
+        }
+    }
+
+    fn detach(&self, key: &[u8], tags: &[String]) {
+        for tag in tags {
+            self.tags.remove_if(tag, |_tag, keys| {
+                keys.remove(key);
+                keys.is_empty()
+            });
+        }
+    }
+
+    /// Same as DashmapCache::cached(), the value being shared rather than copied out of the cache
+    /// Concurrent misses on a key each run the closure, the last value computed is kept
+    pub fn cached<F, A, V>(
+        &self,
+        invalidate_keys: &[String],
+        closure: F,
+        arg: A,
+    ) -> Result<Arc<V>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        self.cached_for(invalidate_keys, None, closure, arg)
+    }
+
+    /// Same as cached(), the value being dropped from the cache once ttl has elapsed
+    pub fn cached_with_ttl<F, A, V>(
+        &self,
+        invalidate_keys: &[String],
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<Arc<V>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        self.cached_for(invalidate_keys, Some(ttl), closure, arg)
+    }
+
+    fn cached_for<F, A, V>(
+        &self,
+        invalidate_keys: &[String],
+        ttl: Option<Duration>,
+        closure: F,
+        arg: A,
+    ) -> Result<Arc<V>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        let key = Self::key_of(&arg)?;
+        if let Some(val) = self.lookup(&key) {
+            return Ok(val);
+        }
+        StatCounters::incr(&self.stats.misses, 1);
+        let epochs = self.epochs_of(invalidate_keys);
+        let val = Arc::new(closure(&arg));
+        self.store(invalidate_keys, key, val.clone(), ttl, &epochs);
+        Ok(val)
+    }
+
+    /// Async version of cached()
+    pub async fn async_cached<F, Fut, A, V>(
+        &self,
+        invalidate_keys: &[String],
+        closure: F,
+        arg: A,
+    ) -> Result<Arc<V>, CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        let key = Self::key_of(&arg)?;
+        if let Some(val) = self.lookup(&key) {
+            return Ok(val);
+        }
+        StatCounters::incr(&self.stats.misses, 1);
+        let epochs = self.epochs_of(invalidate_keys);
+        let val = Arc::new(closure(arg).await);
+        self.store(invalidate_keys, key, val.clone(), None, &epochs);
+        Ok(val)
+    }
+
+    /// Value cached for arg, without computing it on a miss
+    pub fn get<A, V>(&self, arg: &A) -> Result<Option<Arc<V>>, CacheError>
+    where
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        let val = self.lookup(&Self::key_of(arg)?);
+        if val.is_none() {
+            StatCounters::incr(&self.stats.misses, 1);
+        }
+        Ok(val)
+    }
+
+    /// Caches value for arg, replacing any previous value, and returns the shared copy
+    pub fn put<A, V>(
+        &self,
+        invalidate_keys: &[String],
+        arg: &A,
+        value: V,
+    ) -> Result<Arc<V>, CacheError>
+    where
+        A: Serialize,
+        V: Send + Sync + 'static,
+    {
+        let val = Arc::new(value);
+        self.insert(invalidate_keys, Self::key_of(arg)?, val.clone(), None);
+        Ok(val)
+    }
+
+    /// Returns whether a value was cached for arg
+    pub fn remove<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        let Some((key, entry)) = self.inner.remove(&Self::key_of(arg)?) else {
+            return Ok(false);
+        };
+        self.detach(&key, &entry.tags);
+        Ok(true)
+    }
+
+    pub fn contains<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self
+            .inner
+            .get(&Self::key_of(arg)?)
+            .is_some_and(|entry| !entry.is_expired(Instant::now())))
+    }
+
+    /// Same as DashmapCache::invalidate(), values handed out before stay valid for their holders
+    pub fn invalidate(&self, tag: &str) {
+        self.tag_epochs.bump(tag);
+        if let Some((_tag, keys)) = self.tags.remove(tag) {
+            for key in keys {
+                if let Some((key, entry)) = self.inner.remove(&key) {
+                    StatCounters::incr(&self.stats.invalidations, 1);
+                    self.detach(&key, &entry.tags);
+                }
+            }
+        }
+    }
+
+    /// Removes every entry and tag, values being computed meanwhile are discarded
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        let removed = self.inner.len() as u64;
+        self.inner.clear();
+        self.tags.clear();
+        StatCounters::incr(&self.stats.invalidations, removed);
+    }
+
+    /// Removes every expired entry, returns how many were dropped
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<Vec<u8>> = self
+            .inner
+            .iter()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| entry.key().clone())
+            .collect();
+        expired
+            .iter()
+            .filter(|key| self.remove_expired(key))
+            .count()
+    }
+
+    /// Number of entries, including expired ones that were not purged yet
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Same counters as DashmapCache::stats(), bytes staying at 0 as values are not serialized
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+}
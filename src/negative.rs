@@ -0,0 +1,59 @@
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::time::Duration;
+
+use crate::entry::Expiry;
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Results that cached_with_negative() caches for a shorter time when they are negative
+pub trait NegativeOutcome {
+    fn is_negative(&self) -> bool;
+}
+
+/// None is negative
+impl<T> NegativeOutcome for Option<T> {
+    fn is_negative(&self) -> bool {
+        self.is_none()
+    }
+}
+
+/// Err is negative, unlike try_cached() the error is cached and returned as a value
+impl<T, E> NegativeOutcome for Result<T, E> {
+    fn is_negative(&self) -> bool {
+        self.is_err()
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Same as cached(), negative values only being kept for negative_ttl
+    /// Repeated lookups of something missing don't all reach the backing store, yet it is looked
+    /// up again soon after it shows up
+    pub fn cached_with_negative<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        negative_ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b> + NegativeOutcome,
+    {
+        let positive = self.default_expiry();
+        self.try_cached_at(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |val: &V| {
+                if val.is_negative() {
+                    Expiry::after(negative_ttl)
+                } else {
+                    positive
+                }
+            },
+            |arg| Ok(closure(arg)),
+            arg,
+        )
+    }
+}
@@ -0,0 +1,180 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// What becomes of a closure panicking while it computes a miss, see DashmapCacheBuilder::panic_policy()
+/// Nothing is cached for the panicking computation whatever the policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanicPolicy {
+    /// The panic unwinds through the cache call into the caller
+    #[default]
+    Propagate,
+    /// The call fails with CacheError::Panicked
+    Error,
+    /// Same as Error, calls for the same key failing the same way for the given time without
+    /// running the closure
+    CacheFor(#[serde(with = "crate::duration")] Duration),
+}
+
+/// Message of the panic a computation was stopped by, and until when calls for its key replay it
+#[derive(Clone, Debug)]
+pub(crate) struct CachedPanic {
+    message: String,
+    until: Instant,
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(|| "computation panicked".to_owned(), |m| (*m).to_owned()),
+    }
+}
+
+/// Future catching the panics of its inner future, see DashmapCache::guard_panic_async()
+struct CatchUnwind<Fut> {
+    inner: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for CatchUnwind<Fut> {
+    type Output = Result<Fut::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.inner.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(val)) => Poll::Ready(Ok(val)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Error replayed for key while the panic of its last computation is cached
+    pub(crate) fn cached_panic(&self, key: &[u8]) -> Option<CacheError> {
+        let panicked = self.panics.get(key)?;
+        if panicked.until > Instant::now() {
+            return Some(CacheError::Panicked(panicked.message.clone()));
+        }
+        drop(panicked);
+        self.panics
+            .remove_if(key, |_key, panicked| panicked.until <= Instant::now());
+        None
+    }
+
+    /// First error replayed for one of keys, see cached_panic()
+    pub(crate) fn cached_panic_of<K: AsRef<[u8]>>(&self, keys: &[K]) -> Option<CacheError> {
+        if self.panics.is_empty() {
+            return None;
+        }
+        keys.iter().find_map(|key| self.cached_panic(key.as_ref()))
+    }
+
+    /// Drops the panic cached for key, once a refresh computed a value for it
+    pub(crate) fn forget_panic(&self, key: &[u8]) {
+        if !self.panics.is_empty() {
+            self.panics.remove(key);
+        }
+    }
+
+    /// Runs compute, the computation of keys, handling its panics according to the panic policy
+    pub(crate) fn guard_panic<K: AsRef<[u8]>, R>(
+        &self,
+        keys: &[K],
+        compute: impl FnOnce() -> R,
+    ) -> Result<R, CacheError> {
+        if self.panic_policy == PanicPolicy::Propagate {
+            return Ok(compute());
+        }
+        panic::catch_unwind(AssertUnwindSafe(compute))
+            .map_err(|payload| self.panicked(keys, payload))
+    }
+
+    /// Async version of guard_panic(), a panic while polling fut being caught
+    pub(crate) async fn guard_panic_async<K: AsRef<[u8]>, Fut: Future>(
+        &self,
+        keys: &[K],
+        fut: Fut,
+    ) -> Result<Fut::Output, CacheError> {
+        if self.panic_policy == PanicPolicy::Propagate {
+            return Ok(fut.await);
+        }
+        CatchUnwind {
+            inner: Box::pin(fut),
+        }
+        .await
+        .map_err(|payload| self.panicked(keys, payload))
+    }
+
+    fn panicked<K: AsRef<[u8]>>(&self, keys: &[K], payload: Box<dyn Any + Send>) -> CacheError {
+        let message = panic_message(payload);
+        if let PanicPolicy::CacheFor(ttl) = self.panic_policy {
+            let until = Instant::now() + ttl;
+            for key in keys {
+                let panicked = CachedPanic {
+                    message: message.clone(),
+                    until,
+                };
+                self.panics.insert(key.as_ref().to_vec(), panicked);
+            }
+        }
+        CacheError::Panicked(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn cache(policy: PanicPolicy) -> DashmapCache {
+        DashmapCache::builder().panic_policy(policy).build()
+    }
+
+    #[test]
+    fn raw_and_batch_methods_report_panics() {
+        let cache = cache(PanicPolicy::Error);
+        let res = cache.cached_bytes(&vec![], |_x: &u32| -> u32 { panic!("raw") }, 1);
+        assert!(matches!(res, Err(CacheError::Panicked(msg)) if msg == "raw"));
+        let res = cache.cached_many(
+            &vec![],
+            |_xs: &[u32]| -> Vec<u32> { panic!("many") },
+            vec![1, 2],
+        );
+        assert!(matches!(res, Err(CacheError::Panicked(msg)) if msg == "many"));
+        let res = cache.refresh_cache(&vec![], |_x: &u32| -> u32 { panic!("refresh") }, 1);
+        assert!(matches!(res, Err(CacheError::Panicked(msg)) if msg == "refresh"));
+        assert!(cache.cached_bytes(&vec![], |x: &u32| *x, 1).is_ok());
+    }
+
+    #[test]
+    fn cached_panics_replay_until_a_refresh() {
+        let cache = cache(PanicPolicy::CacheFor(Duration::from_secs(60)));
+        let calls = Cell::new(0);
+        let panicking = |_xs: &[u32]| -> Vec<u32> {
+            calls.set(calls.get() + 1);
+            panic!("batch")
+        };
+        assert!(cache.cached_many(&vec![], panicking, vec![1, 2]).is_err());
+        let res = cache.cached_many(&vec![], |xs: &[u32]| xs.to_vec(), vec![2]);
+        assert!(matches!(res, Err(CacheError::Panicked(_))));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(
+            cache.refresh_cache(&vec![], |x: &u32| x * 10, 2).unwrap(),
+            20
+        );
+        assert_eq!(
+            cache
+                .cached_many(&vec![], |xs: &[u32]| xs.to_vec(), vec![2])
+                .unwrap(),
+            vec![20]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// invalidate() for every tag starting with prefix, returns the tags that were invalidated
+    /// Only tags of cached entries are looked at, as with invalidate_where()
+    pub fn invalidate_prefix(&self, prefix: &str) -> Vec<String> {
+        self.invalidate_where(|tag| tag.starts_with(prefix))
+    }
+
+    /// invalidate() for every tag matching the glob pattern, returns the tags that were invalidated
+    /// `*` matches any run of characters, `?` any single character, the rest matches itself
+    /// Only tags of cached entries are looked at, as with invalidate_where()
+    pub fn invalidate_matching(&self, pattern: &str) -> Vec<String> {
+        self.invalidate_where(|tag| glob_match(pattern, tag))
+    }
+
+    /// invalidate() for every tag predicate accepts, returns the tags that were invalidated
+    /// Only tags of cached entries are looked at: tags present only in the backend, or only on
+    /// values still being computed, are not invalidated
+    pub fn invalidate_where<P: Fn(&str) -> bool>(&self, predicate: P) -> Vec<String> {
+        self.apply_remote_invalidations();
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|tag| predicate(tag.key()))
+            .map(|tag| tag.key().clone())
+            .collect();
+        for tag in &tags {
+            self.invalidate(tag);
+        }
+        tags
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it was matched against, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_runs_and_single_characters() {
+        assert!(glob_match("user:*", "user:"));
+        assert!(glob_match("user:*", "user:42"));
+        assert!(!glob_match("user:*", "users:42"));
+        assert!(glob_match("*:42", "user:42"));
+        assert!(glob_match("u*r:*2", "user:42"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("user:?", "user:4"));
+        assert!(!glob_match("user:?", "user:"));
+        assert!(!glob_match("user:?", "user:42"));
+        assert!(glob_match("user:??", "user:42"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "user"));
+        assert!(!glob_match("user", ""));
+    }
+
+    #[test]
+    fn globs_match_multi_byte_characters_whole() {
+        assert!(glob_match("caf?", "café"));
+        assert!(glob_match("?:*", "é:ü"));
+        assert!(glob_match("*ü", "grüßü"));
+        assert!(glob_match("gr??e", "grüße"));
+        assert!(!glob_match("gr?e", "grüße"));
+        assert!(glob_match("日本*", "日本語"));
+    }
+
+    fn cache() -> DashmapCache {
+        let cache = DashmapCache::new();
+        for (i, tag) in ["user:1", "user:2", "users", "team:1", "ユーザー:1"]
+            .into_iter()
+            .enumerate()
+        {
+            cache.put(&vec![tag.to_owned()], &i, &i).unwrap();
+        }
+        cache
+    }
+
+    fn sorted(mut tags: Vec<String>) -> Vec<String> {
+        tags.sort_unstable();
+        tags
+    }
+
+    #[test]
+    fn matching_tags_are_invalidated() {
+        let cache = cache();
+        assert_eq!(
+            sorted(cache.invalidate_matching("user:?")),
+            ["user:1", "user:2"]
+        );
+        assert!(!cache.contains(&0usize).unwrap());
+        assert!(!cache.contains(&1usize).unwrap());
+        assert!(cache.contains(&2usize).unwrap());
+        assert_eq!(cache.invalidate_matching("ユーザー:*"), ["ユーザー:1"]);
+        assert!(!cache.contains(&4usize).unwrap());
+        assert!(cache.invalidate_matching("").is_empty());
+        assert!(cache.invalidate_matching("nothing*").is_empty());
+        assert_eq!(sorted(cache.invalidate_matching("*")), ["team:1", "users"]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn prefixed_tags_are_invalidated() {
+        let cache = cache();
+        assert_eq!(
+            sorted(cache.invalidate_prefix("user")),
+            ["user:1", "user:2", "users"]
+        );
+        assert!(cache.contains(&3usize).unwrap());
+        assert!(cache.invalidate_prefix("user").is_empty());
+        assert_eq!(cache.invalidate_prefix("ユー"), ["ユーザー:1"]);
+        assert_eq!(cache.invalidate_prefix(""), ["team:1"]);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+
+use crate::entry::Entry;
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Bytes the serializer wrote for the value cached for arg, without decoding them
+    /// Saves the decoding when the bytes are forwarded as they are, as the body of a response for instance
+    pub fn get_raw<A: Serialize>(&self, arg: &A) -> Result<Option<Vec<u8>>, CacheError> {
+        let key = self.key_of(arg)?;
+        let raw = self.lookup_raw(&key)?;
+        if raw.is_none() {
+            StatCounters::incr(&self.stats.misses, 1);
+        }
+        Ok(raw)
+    }
+
+    /// Runs f on the bytes the serializer wrote for the value cached for arg, without copying them
+    /// unless they were compressed
+    /// The entry is locked for reading while f runs: f must not write to the cache
+    pub fn with_raw<A, R, F>(&self, arg: &A, f: F) -> Result<Option<R>, CacheError>
+    where
+        A: Serialize,
+        F: FnOnce(&[u8]) -> R,
+    {
+        let key = self.key_of(arg)?;
+        if let Some(entry) = self.live_entry(&key) {
+            StatCounters::incr(&self.stats.hits, 1);
+            return Ok(Some(f(&self.raw_value(&entry.value)?)));
+        }
+        match self.fetch_backend(&key) {
+            Some(stored) => Ok(Some(f(&self.raw_value(&stored)?))),
+            None => {
+                StatCounters::incr(&self.stats.misses, 1);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Same as cached(), returning the bytes the serializer wrote for the value instead of the value
+    pub fn cached_bytes<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<Vec<u8>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let (raw, _outcome) = self.single_flight(
+            self.key_of(&arg)?,
+            invalidate_keys,
+            |stored| Ok(self.raw_value(stored)?.into_owned()),
+            || Ok::<_, CacheError>(closure(&arg)),
+            |val| {
+                let mut buf = Vec::new();
+                self.encode_into(&val, &mut buf)?;
+                let stored = self.compressed(&buf)?.unwrap_or_else(|| buf.clone());
+                if self.framed() {
+                    buf.remove(0);
+                }
+                Ok((buf, Some(Entry::new(stored, self.default_expiry()))))
+            },
+        )?;
+        Ok(raw)
+    }
+
+    fn lookup_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, CacheError> {
+        if let Some(entry) = self.live_entry(key) {
+            StatCounters::incr(&self.stats.hits, 1);
+            return Ok(Some(self.raw_value(&entry.value)?.into_owned()));
+        }
+        match self.fetch_backend(key) {
+            Some(stored) => Ok(Some(self.raw_value(&stored)?.into_owned())),
+            None => Ok(None),
+        }
+    }
+}
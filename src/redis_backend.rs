@@ -0,0 +1,347 @@
+use core::fmt;
+use redis::{Client, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::invalidation::origin_id;
+use crate::{
+    BackendEntry, CacheBackend, CacheError, InvalidationHandler, InvalidationTransport,
+    RemoteInvalidation, Subscription,
+};
+
+/// Envelope of a value stored in Redis
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    #[serde(with = "serde_bytes")]
+    value: Vec<u8>,
+    tags: Vec<String>,
+}
+
+/// Writes KEYS[1] with ARGV[1] and a ttl of ARGV[2] ms, none if 0, tagging it with the sets KEYS[3..]
+/// KEYS[2] lists the sets the key is in, so that the key leaves those it is no longer tagged with
+/// A set lasts as long as the longest lived of its keys, and each write drops a few expired keys
+/// from the sets it touches
+const INSERT: &str = r"
+for _, set in ipairs(redis.call('SMEMBERS', KEYS[2])) do redis.call('SREM', set, KEYS[1]) end
+redis.call('DEL', KEYS[2])
+local ttl = tonumber(ARGV[2])
+if ttl > 0 then
+  redis.call('SET', KEYS[1], ARGV[1], 'PX', ttl)
+else
+  redis.call('SET', KEYS[1], ARGV[1])
+end
+for i = 3, #KEYS do
+  local set = KEYS[i]
+  local fresh = redis.call('EXISTS', set) == 0
+  for _, key in ipairs(redis.call('SRANDMEMBER', set, 4)) do
+    if redis.call('EXISTS', key) == 0 then redis.call('SREM', set, key) end
+  end
+  redis.call('SADD', set, KEYS[1])
+  redis.call('SADD', KEYS[2], set)
+  if ttl == 0 then
+    redis.call('PERSIST', set)
+  elseif fresh then
+    redis.call('PEXPIRE', set, ttl)
+  else
+    local left = redis.call('PTTL', set)
+    if left >= 0 and left < ttl then redis.call('PEXPIRE', set, ttl) end
+  end
+end
+if ttl > 0 and #KEYS > 2 then redis.call('PEXPIRE', KEYS[2], ttl) end
+";
+
+/// Removes KEYS[1] and takes it out of the sets listed by KEYS[2], returning whether it existed
+const REMOVE: &str = r"
+for _, set in ipairs(redis.call('SMEMBERS', KEYS[2])) do redis.call('SREM', set, KEYS[1]) end
+redis.call('DEL', KEYS[2])
+return redis.call('DEL', KEYS[1])
+";
+
+/// Removes the keys of the set KEYS[1] and the set, taking the keys out of their other sets
+/// ARGV[1] is the prefix, the sets of `<prefix>k:<key>` being listed by `<prefix>g:<key>`
+const INVALIDATE: &str = r"
+for _, key in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+  local sets = ARGV[1] .. 'g:' .. string.sub(key, #ARGV[1] + 3)
+  for _, set in ipairs(redis.call('SMEMBERS', sets)) do
+    if set ~= KEYS[1] then redis.call('SREM', set, key) end
+  end
+  redis.call('DEL', sets, key)
+end
+redis.call('DEL', KEYS[1])
+";
+
+fn backend_error(err: redis::RedisError) -> CacheError {
+    CacheError::Backend(Box::new(err))
+}
+
+/// Connection opened on first use and opened again after a failure
+struct SharedConnection {
+    client: Client,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl SharedConnection {
+    fn open(url: &str) -> Result<Self, CacheError> {
+        Ok(Self {
+            client: Client::open(url).map_err(backend_error)?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn query<T>(
+        &self,
+        op: impl FnOnce(&mut Connection) -> RedisResult<T>,
+    ) -> Result<T, CacheError> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let con = match &mut *connection {
+            Some(con) => con,
+            None => connection.insert(self.client.get_connection().map_err(backend_error)?),
+        };
+        op(con).map_err(|err| {
+            *connection = None;
+            backend_error(err)
+        })
+    }
+}
+
+/// CacheBackend storing entries in Redis, keys are `<prefix>k:<key>` and tag sets `<prefix>t:<tag>`,
+/// `<prefix>g:<key>` listing the tag sets of each key so that removals keep the sets in step
+/// Writes and invalidations run as Lua scripts reading the keys of the sets, so a cluster isn't supported
+/// Queries block the calling thread, async lookups included: a cache using a RedisBackend from async
+/// code needs a multi-threaded tokio runtime, or its calls wrapped in spawn_blocking
+pub struct RedisBackend {
+    connection: SharedConnection,
+    prefix: Vec<u8>,
+}
+
+impl fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("client", &self.connection.client)
+            .field("prefix", &String::from_utf8_lossy(&self.prefix))
+            .finish()
+    }
+}
+
+impl RedisBackend {
+    /// Backend on the Redis server at url, such as `redis://127.0.0.1/`, with the `dashmap-cache:` prefix
+    /// The connection is only opened on first use
+    pub fn open(url: &str) -> Result<Self, CacheError> {
+        Ok(Self {
+            connection: SharedConnection::open(url)?,
+            prefix: b"dashmap-cache:".to_vec(),
+        })
+    }
+
+    /// Prefix of every Redis key written, so that several caches can share a server
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.as_bytes().to_vec();
+        self
+    }
+
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.prefix, b"k:".as_slice(), key].concat()
+    }
+
+    fn tag_key(&self, tag: &str) -> Vec<u8> {
+        [&self.prefix, b"t:".as_slice(), tag.as_bytes()].concat()
+    }
+
+    /// Set of the tag sets key is in
+    fn sets_key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.prefix, b"g:".as_slice(), key].concat()
+    }
+
+    fn query<T>(
+        &self,
+        op: impl FnOnce(&mut Connection) -> RedisResult<T>,
+    ) -> Result<T, CacheError> {
+        self.connection.query(op)
+    }
+}
+
+impl CacheBackend for RedisBackend {
+    fn get(&self, key: &[u8]) -> Result<Option<BackendEntry>, CacheError> {
+        let key = self.key(key);
+        let (stored, pttl): (Option<Vec<u8>>, i64) = self.query(|con| {
+            redis::pipe()
+                .cmd("GET")
+                .arg(&key)
+                .cmd("PTTL")
+                .arg(&key)
+                .query(con)
+        })?;
+        let Some(stored) = stored else {
+            return Ok(None);
+        };
+        let stored: Stored = rmp_serde::from_slice(&stored)?;
+        Ok(Some(BackendEntry {
+            value: stored.value,
+            tags: stored.tags,
+            ttl: u64::try_from(pttl).ok().map(Duration::from_millis),
+        }))
+    }
+
+    fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        tags: &[String],
+        ttl: Option<Duration>,
+    ) -> Result<(), CacheError> {
+        let stored = rmp_serde::to_vec(&Stored {
+            value: value.to_vec(),
+            tags: tags.to_vec(),
+        })?;
+        let ttl = ttl.map_or(0, |ttl| ttl.as_millis().max(1) as u64);
+        let mut insert = redis::cmd("EVAL");
+        insert
+            .arg(INSERT)
+            .arg(2 + tags.len())
+            .arg(self.key(key))
+            .arg(self.sets_key(key));
+        for tag in tags {
+            insert.arg(self.tag_key(tag));
+        }
+        insert.arg(stored).arg(ttl);
+        self.query(|con| insert.query::<()>(con))
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<bool, CacheError> {
+        let mut remove = redis::cmd("EVAL");
+        remove
+            .arg(REMOVE)
+            .arg(2)
+            .arg(self.key(key))
+            .arg(self.sets_key(key));
+        let removed: u64 = self.query(|con| remove.query(con))?;
+        Ok(removed > 0)
+    }
+
+    fn invalidate_tag(&self, tag: &str) -> Result<(), CacheError> {
+        let mut invalidate = redis::cmd("EVAL");
+        invalidate
+            .arg(INVALIDATE)
+            .arg(1)
+            .arg(self.tag_key(tag))
+            .arg(&self.prefix);
+        self.query(|con| invalidate.query::<()>(con))
+    }
+}
+
+/// InvalidationTransport over Redis pub/sub, every instance publishing to and subscribing on one channel
+/// A subscriber reconnects every second after losing its connection, the cache it delivers to then
+/// dropping its local entries since tags published meanwhile were missed
+pub struct RedisTransport {
+    connection: SharedConnection,
+    channel: String,
+    origin: u64,
+}
+
+impl fmt::Debug for RedisTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisTransport")
+            .field("client", &self.connection.client)
+            .field("channel", &self.channel)
+            .finish()
+    }
+}
+
+impl RedisTransport {
+    /// Transport on the `dashmap-cache:invalidations` channel of the Redis server at url
+    pub fn open(url: &str) -> Result<Self, CacheError> {
+        Ok(Self {
+            connection: SharedConnection::open(url)?,
+            channel: "dashmap-cache:invalidations".to_owned(),
+            origin: origin_id(),
+        })
+    }
+
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = channel.to_owned();
+        self
+    }
+}
+
+/// Clones publish on the same channel with a connection of their own, each acting as a separate instance
+impl Clone for RedisTransport {
+    fn clone(&self) -> Self {
+        Self {
+            connection: SharedConnection {
+                client: self.connection.client.clone(),
+                connection: Mutex::new(None),
+            },
+            channel: self.channel.clone(),
+            origin: origin_id(),
+        }
+    }
+}
+
+impl InvalidationTransport for RedisTransport {
+    fn publish(&self, tag: &str) -> Result<(), CacheError> {
+        let message = rmp_serde::to_vec(&(self.origin, tag))?;
+        self.connection.query(|con| {
+            redis::cmd("PUBLISH")
+                .arg(&self.channel)
+                .arg(message)
+                .query::<()>(con)
+        })
+    }
+
+    fn subscribe(&self, on_tag: InvalidationHandler) -> Result<Subscription, CacheError> {
+        let client = self.connection.client.clone();
+        let channel = self.channel.clone();
+        let origin = self.origin;
+        let mut con = client.get_connection().map_err(backend_error)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        std::thread::Builder::new()
+            .name("dashmap-cache-invalidations".to_owned())
+            .spawn(move || loop {
+                // Reads time out so that the thread notices the subscription being dropped
+                let _ = con.set_read_timeout(Some(Duration::from_secs(1)));
+                let mut pubsub = con.as_pubsub();
+                if pubsub.subscribe(&channel).is_ok() {
+                    loop {
+                        match pubsub.get_message() {
+                            Ok(message) => {
+                                let Ok((from, tag)) = rmp_serde::from_slice::<(u64, String)>(
+                                    message.get_payload_bytes(),
+                                ) else {
+                                    continue;
+                                };
+                                if from != origin && !on_tag(RemoteInvalidation::Tag(tag)) {
+                                    return;
+                                }
+                            }
+                            Err(err) if err.is_timeout() && !stop.load(Ordering::Acquire) => {}
+                            Err(_) => break,
+                        }
+                    }
+                }
+                drop(pubsub);
+                con = loop {
+                    if stop.load(Ordering::Acquire) {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_secs(1));
+                    if let Ok(con) = client.get_connection() {
+                        break con;
+                    }
+                };
+                // Whatever was published while disconnected is gone
+                if !on_tag(RemoteInvalidation::Lost) {
+                    return;
+                }
+            })?;
+        Ok(Subscription::new(move || {
+            stopped.store(true, Ordering::Release)
+        }))
+    }
+
+    fn fork(&self) -> Option<Arc<dyn InvalidationTransport>> {
+        Some(Arc::new(self.clone()))
+    }
+}
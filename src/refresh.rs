@@ -0,0 +1,131 @@
+use core::slice;
+use serde::Serialize;
+use std::marker::{Send, Sync};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use crate::cancel::AbortOnDrop;
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer + 'static> DashmapCache<S> {
+    /// Keeps the entry for arg warm: a background task computes it right away, then replaces it every interval
+    /// The entry is tagged with tag, invalidating it only lasts until the next refresh
+    /// The task runs until the returned handle is aborted or the cache is dropped
+    /// Each refresh holds the key lock like refresh_cache() does and is computed like a miss of
+    /// tokio_cached(): its task is aborted past the builder timeout and its panics follow the panic policy
+    /// Errors, failed joins and timeouts included, are kept for take_last_error()
+    pub fn refresh_every<F, A, V>(
+        self: &Arc<Self>,
+        tag: &str,
+        interval: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<JoinHandle<()>, CacheError>
+    where
+        F: Fn(&A) -> JoinHandle<V> + Send + 'static,
+        A: Sync + Send + Serialize + 'static,
+        V: Serialize + Send + 'static,
+    {
+        let arg_bytes = self.key_of(&arg)?;
+        let tags = vec![tag.to_owned()];
+        let cache = Arc::downgrade(self);
+        Ok(tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                let _flight = cache.lock_key_at_async(arg_bytes.clone()).await;
+                let epochs = cache.epochs_of(&tags);
+                let refreshed = cache
+                    .compute_future(slice::from_ref(&arg_bytes), &tags, || {
+                        let handle = AbortOnDrop(closure(&arg));
+                        async move { handle.await.map_err(CacheError::from) }
+                    })
+                    .await
+                    .and_then(|val| cache.encode(&val));
+                match refreshed {
+                    Ok(val_bytes) => cache.store_refreshed(&tags, &arg_bytes, val_bytes, &epochs),
+                    Err(err) => cache.record_error(err),
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PanicPolicy;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Sets its flag when dropped, telling that the task owning it was aborted
+    struct Dropped(Arc<AtomicBool>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_refreshes_are_aborted_past_the_timeout() {
+        let cache = Arc::new(
+            DashmapCache::builder()
+                .timeout(Duration::from_millis(10))
+                .build(),
+        );
+        let aborted = Arc::new(AtomicBool::new(false));
+        let flag = aborted.clone();
+        let refresher = cache
+            .refresh_every(
+                "slow",
+                Duration::from_secs(60),
+                move |_x: &u64| {
+                    let dropped = Dropped(flag.clone());
+                    tokio::spawn(async move {
+                        let _dropped = dropped;
+                        std::future::pending::<u64>().await
+                    })
+                },
+                1u64,
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(matches!(cache.take_last_error(), Some(CacheError::Timeout)));
+        assert!(aborted.load(Ordering::SeqCst));
+        assert!(!cache.contains(&1u64).unwrap());
+        refresher.abort();
+    }
+
+    #[tokio::test]
+    async fn panicking_refreshes_follow_the_panic_policy() {
+        let cache = Arc::new(
+            DashmapCache::builder()
+                .panic_policy(PanicPolicy::Error)
+                .build(),
+        );
+        let refresher = cache
+            .refresh_every(
+                "panics",
+                Duration::from_millis(10),
+                |x: &u64| -> JoinHandle<u64> {
+                    if *x == 1 {
+                        panic!("no task");
+                    }
+                    tokio::spawn(std::future::ready(*x))
+                },
+                1u64,
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!refresher.is_finished());
+        let err = cache.take_last_error();
+        assert!(matches!(err, Some(CacheError::Panicked(msg)) if msg == "no task"));
+        refresher.abort();
+    }
+}
@@ -0,0 +1,254 @@
+use core::future::Future;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::time::Duration;
+
+use crate::entry::Expiry;
+use crate::{CacheError, DashmapCache, MsgPack, Serializer};
+
+/// Handle on a DashmapCache whose keys and tags are those of a named scope, see DashmapCache::scope()
+/// Scoped keys are prefixed with the encoded scope name, and scoped tags are stored length-prefixed
+/// after a NUL character, see tag(), every entry of the scope also carrying the scope-wide tag
+/// Scopes sharing a cache never share entries or tags, nor with the cache's own tags as long as
+/// those don't start with NUL
+#[derive(Debug)]
+pub struct Scope<'a, S: Serializer = MsgPack> {
+    cache: &'a DashmapCache<S>,
+    name: String,
+    prefix: Vec<u8>,
+}
+
+impl<S: Serializer> Clone for Scope<'_, S> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache,
+            name: self.name.clone(),
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Handle keyed and tagged under the name scope
+    pub fn scope(&self, name: &str) -> Scope<'_, S> {
+        Scope {
+            cache: self,
+            name: name.to_owned(),
+            prefix: rmp_serde::to_vec(&("scope", name)).expect("a str always encodes"),
+        }
+    }
+}
+
+impl<S: Serializer> Scope<'_, S> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name the cache stores tag of the scope under, `\0scope:<name length>:<name>:<tag>`
+    /// The name length makes it unique to the scope and tag pair whatever the characters they hold
+    pub fn tag(&self, tag: &str) -> String {
+        format!("{}:{tag}", self.all_tag())
+    }
+
+    /// Tag every entry of the scope carries, `\0scope:<name length>:<name>`
+    fn all_tag(&self) -> String {
+        format!("\0scope:{}:{}", self.name.len(), self.name)
+    }
+
+    /// Takes the &Vec<String> all the tag arguments of the cache take
+    #[allow(clippy::ptr_arg)]
+    fn tags(&self, invalidate_keys: &Vec<String>) -> Vec<String> {
+        invalidate_keys
+            .iter()
+            .map(|tag| self.tag(tag))
+            .chain([self.all_tag()])
+            .collect()
+    }
+
+    fn key<A: Serialize>(&self, arg: &A) -> Result<Vec<u8>, CacheError> {
+        let mut key = self.prefix.clone();
+        rmp_serde::encode::write(&mut key, arg)?;
+        Ok(self.cache.digest_key(key))
+    }
+
+    /// Scoped version of DashmapCache::invalidate()
+    pub fn invalidate(&self, tag: &str) {
+        self.cache.invalidate(&self.tag(tag));
+    }
+
+    /// Removes every entry of the scope, leaving the rest of the cache as it is
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate(&self.all_tag());
+    }
+
+    /// Scoped version of DashmapCache::remove()
+    pub fn remove<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.cache.remove_key(&self.key(arg)?))
+    }
+
+    /// Scoped version of DashmapCache::contains()
+    pub fn contains<A: Serialize>(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.cache.contains_key(&self.key(arg)?))
+    }
+
+    /// Scoped version of DashmapCache::get()
+    pub fn get<A, V>(&self, arg: &A) -> Result<Option<V>, CacheError>
+    where
+        A: Serialize,
+        V: for<'b> Deserialize<'b>,
+    {
+        self.cache.get_key(&self.key(arg)?)
+    }
+
+    /// Scoped version of DashmapCache::put()
+    pub fn put<A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        arg: &A,
+        value: &V,
+    ) -> Result<(), CacheError>
+    where
+        A: Serialize,
+        V: Serialize,
+    {
+        self.cache
+            .put_key(&self.tags(invalidate_keys), &self.key(arg)?, value)
+    }
+
+    /// Scoped version of DashmapCache::cached()
+    pub fn cached<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cache.cached_at(
+            self.key(&arg)?,
+            &self.tags(invalidate_keys),
+            self.cache.default_expiry(),
+            closure,
+            arg,
+        )
+    }
+
+    /// Scoped version of DashmapCache::cached_with_ttl()
+    pub fn cached_with_ttl<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cache.cached_at(
+            self.key(&arg)?,
+            &self.tags(invalidate_keys),
+            Expiry::after(ttl),
+            closure,
+            arg,
+        )
+    }
+
+    /// Scoped version of DashmapCache::try_cached()
+    pub fn try_cached<F, A, V, E>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, E>
+    where
+        F: Fn(&A) -> Result<V, E>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+        E: From<CacheError>,
+    {
+        self.cache.try_cached_at(
+            self.key(&arg)?,
+            &self.tags(invalidate_keys),
+            self.cache.default_expiry(),
+            closure,
+            arg,
+        )
+    }
+
+    /// Scoped version of DashmapCache::async_cached()
+    pub async fn async_cached<F, Fut, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cache
+            .async_cached_at(
+                self.key(&arg)?,
+                &self.tags(invalidate_keys),
+                self.cache.default_expiry(),
+                closure,
+                arg,
+            )
+            .await
+    }
+
+    /// Scoped version of DashmapCache::tokio_cached()
+    #[cfg(feature = "tokio")]
+    pub async fn tokio_cached<F, A, V>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        self.cache
+            .tokio_cached_at(
+                self.key(&arg)?,
+                &self.tags(invalidate_keys),
+                self.cache.default_expiry(),
+                closure,
+                arg,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_tags_never_collide() {
+        let cache = DashmapCache::new();
+        let (outer, inner) = (cache.scope("a"), cache.scope("a/b"));
+        outer.put(&vec!["b/c".to_owned()], &1, &10).unwrap();
+        inner.put(&vec!["c".to_owned()], &1, &20).unwrap();
+        cache.put(&vec!["a".to_owned()], &1, &30).unwrap();
+        assert_ne!(outer.tag("b/c"), inner.tag("c"));
+        inner.invalidate("c");
+        assert_eq!(inner.get::<_, u32>(&1).unwrap(), None);
+        assert_eq!(outer.get::<_, u32>(&1).unwrap(), Some(10));
+        cache.invalidate("a");
+        assert_eq!(outer.get::<_, u32>(&1).unwrap(), Some(10));
+        assert_eq!(cache.get::<_, u32>(&1).unwrap(), None);
+        outer.invalidate_all();
+        assert_eq!(outer.get::<_, u32>(&1).unwrap(), None);
+    }
+}
@@ -0,0 +1,100 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::CacheError;
+
+/// Codec used to store cached values as bytes
+/// Keys are always encoded with MessagePack so that they stay stable whatever codec the values use
+pub trait Serializer: Send + Sync {
+    /// Appends the encoding of val to buf
+    fn encode_into<T: Serialize + ?Sized>(
+        &self,
+        val: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError>;
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError>;
+
+    fn encode<T: Serialize + ?Sized>(&self, val: &T) -> Result<Vec<u8>, CacheError> {
+        let mut buf = Vec::new();
+        self.encode_into(val, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// MessagePack through rmp_serde, the default codec
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsgPack;
+
+impl Serializer for MsgPack {
+    fn encode_into<T: Serialize + ?Sized>(
+        &self,
+        val: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError> {
+        Ok(rmp_serde::encode::write(buf, val)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Serializer for Bincode {
+    fn encode_into<T: Serialize + ?Sized>(
+        &self,
+        val: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError> {
+        bincode::serialize_into(buf, val).map_err(|e| CacheError::Codec(e))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        bincode::deserialize(bytes).map_err(|e| CacheError::Codec(e))
+    }
+}
+
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Serializer for Json {
+    fn encode_into<T: Serialize + ?Sized>(
+        &self,
+        val: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError> {
+        serde_json::to_writer(buf, val).map_err(|e| CacheError::Codec(Box::new(e)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        serde_json::from_slice(bytes).map_err(|e| CacheError::Codec(Box::new(e)))
+    }
+}
+
+#[cfg(feature = "postcard")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Serializer for Postcard {
+    fn encode_into<T: Serialize + ?Sized>(
+        &self,
+        val: &T,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), CacheError> {
+        postcard::to_extend(val, core::mem::take(buf))
+            .map(|encoded| *buf = encoded)
+            .map_err(|e| CacheError::Codec(Box::new(e)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CacheError> {
+        postcard::from_bytes(bytes).map_err(|e| CacheError::Codec(Box::new(e)))
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::entry::{Entry, Expiry};
+use crate::{CacheError, DashmapCache, KeyStrategy, Serializer};
+
+const MAGIC: &[u8; 4] = b"DMC\0";
+const VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    #[serde(with = "serde_bytes")]
+    key: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    value: Vec<u8>,
+    /// Time left before expiry when the snapshot was taken
+    ttl: Option<Duration>,
+    /// Time left before the value turns stale, for entries served stale while revalidating
+    stale: Option<Duration>,
+    tags: Vec<String>,
+    #[serde(default = "unit_weight")]
+    weight: u64,
+}
+
+fn unit_weight() -> u64 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    entries: Vec<SnapshotEntry>,
+    /// Keys of the entries were derived with it
+    #[serde(default)]
+    key_strategy: KeyStrategy,
+    /// Values start with a compression tag
+    #[serde(default)]
+    framed: bool,
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Writes the cached entries and their tags to path
+    /// The file is written next to path then renamed over it, so readers never see a partial snapshot
+    /// Entries written while dumping may or may not be part of the snapshot
+    pub fn dump<P: AsRef<Path>>(&self, path: P) -> Result<(), CacheError> {
+        let now = Instant::now();
+        let snapshot = Snapshot {
+            entries: self
+                .inner
+                .iter()
+                .filter(|entry| !entry.is_expired(now))
+                .map(|entry| SnapshotEntry {
+                    key: entry.key().clone(),
+                    value: entry.value.clone(),
+                    ttl: entry.expires_at.map(|expires_at| expires_at - now),
+                    stale: entry
+                        .stale_at
+                        .map(|stale_at| stale_at.saturating_duration_since(now)),
+                    tags: entry.tags.clone(),
+                    weight: entry.weight,
+                })
+                .collect(),
+            key_strategy: self.key_strategy,
+            framed: self.framed(),
+        };
+
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let file = File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        rmp_serde::encode::write(&mut writer, &snapshot)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Adds the entries of a snapshot written by dump() to the cache
+    /// Values are stored as dumped, so the cache must use the same Serializer as the one that dumped them
+    pub fn restore<P: AsRef<Path>>(&self, path: P) -> Result<(), CacheError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; 6];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(CacheError::Snapshot(
+                "not a dashmap-cache snapshot".to_owned(),
+            ));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(CacheError::Snapshot(format!(
+                "unsupported snapshot version {version}"
+            )));
+        }
+        let snapshot: Snapshot = rmp_serde::from_read(reader)?;
+        if snapshot.key_strategy != self.key_strategy {
+            return Err(CacheError::Snapshot(format!(
+                "snapshot keys use {:?}, the cache uses {:?}",
+                snapshot.key_strategy, self.key_strategy
+            )));
+        }
+        if snapshot.framed != self.framed() {
+            return Err(CacheError::Snapshot(
+                "compression can't be turned on or off between dump and restore".to_owned(),
+            ));
+        }
+
+        for entry in snapshot.entries {
+            let expiry = match (entry.stale, entry.ttl) {
+                (Some(stale), Some(ttl)) => Expiry {
+                    ttl: Some(stale),
+                    stale_ttl: ttl.saturating_sub(stale),
+                },
+                (_, ttl) => Expiry::from_ttl(ttl),
+            };
+            let restored = Entry::new(entry.value, expiry).with_weight(entry.weight);
+            self.insert(&entry.tags, &entry.key, restored);
+        }
+        Ok(())
+    }
+}
+
+impl DashmapCache {
+    /// Creates a default cache filled from a snapshot written by dump()
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CacheError> {
+        let cache = Self::new();
+        cache.restore(path)?;
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Path in the temp directory, removed when dropped
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir();
+            Self(dir.join(format!("dashmap-cache-{}-{name}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn dumped_entries_load_back_with_their_tags_and_ttls() {
+        let path = TempPath::new("round-trip");
+        let cache = DashmapCache::new();
+        let users = vec!["users".to_owned()];
+        cache.cached(&users, |_| "ann".to_owned(), 1u32).unwrap();
+        let ttl = Duration::from_secs(60);
+        cache
+            .cached_with_ttl(&users, ttl, |_| "bob".to_owned(), 2u32)
+            .unwrap();
+        cache.cached(&vec![], |_| "cat".to_owned(), 3u32).unwrap();
+        cache.dump(&path.0).unwrap();
+
+        let loaded = DashmapCache::load(&path.0).unwrap();
+        assert_eq!(loaded.inner.len(), 3);
+        let cached = |arg: u32| loaded.cached(&vec![], |_| String::new(), arg).unwrap();
+        for (arg, name) in [(1u32, "ann"), (2, "bob"), (3, "cat")] {
+            assert_eq!(cached(arg), name);
+        }
+        let expires_at = |arg: u32| {
+            let key = rmp_serde::to_vec(&arg).unwrap();
+            loaded.inner.get(&key).unwrap().expires_at
+        };
+        assert_eq!(expires_at(1), None);
+        let left = expires_at(2).unwrap() - Instant::now();
+        assert!(left > ttl - Duration::from_secs(5) && left <= ttl);
+        assert_eq!(loaded.tags.get("users").unwrap().len(), 2);
+        loaded.invalidate("users");
+        assert_eq!(loaded.inner.len(), 1);
+        assert_eq!(cached(3), "cat");
+    }
+
+    #[test]
+    fn other_files_and_versions_are_rejected() {
+        let path = TempPath::new("rejected");
+        fs::write(&path.0, b"not a snapshot").unwrap();
+        assert!(matches!(
+            DashmapCache::load(&path.0),
+            Err(CacheError::Snapshot(reason)) if reason.contains("not a dashmap-cache snapshot")
+        ));
+        let mut newer = MAGIC.to_vec();
+        newer.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        fs::write(&path.0, &newer).unwrap();
+        assert!(matches!(
+            DashmapCache::load(&path.0),
+            Err(CacheError::Snapshot(reason)) if reason.contains("version")
+        ));
+        fs::write(&path.0, &MAGIC[..2]).unwrap();
+        assert!(matches!(
+            DashmapCache::load(&path.0),
+            Err(CacheError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn corrupt_snapshots_are_rejected() {
+        let path = TempPath::new("corrupt");
+        let cache = DashmapCache::new();
+        for i in 0..10u32 {
+            cache.cached(&vec![format!("tag-{i}")], |x| *x, i).unwrap();
+        }
+        cache.dump(&path.0).unwrap();
+        let dumped = fs::read(&path.0).unwrap();
+
+        fs::write(&path.0, &dumped[..dumped.len() / 2]).unwrap();
+        assert!(matches!(
+            DashmapCache::load(&path.0),
+            Err(CacheError::Decode(_))
+        ));
+        let mut garbled = dumped.clone();
+        garbled[6] = 0xc1;
+        fs::write(&path.0, &garbled).unwrap();
+        assert!(matches!(
+            DashmapCache::load(&path.0),
+            Err(CacheError::Decode(_))
+        ));
+        // A failed restore leaves the cache as it was
+        let target = DashmapCache::new();
+        target.cached(&vec![], |_| 1u8, "kept").unwrap();
+        assert!(target.restore(&path.0).is_err());
+        assert_eq!(target.inner.len(), 1);
+    }
+}
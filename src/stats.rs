@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::entry::Entry;
+use crate::instrument::{default_name, record_contents, CacheName, Counter};
+use crate::{DashmapCache, Serializer};
+
+/// Snapshot of the cache counters, see DashmapCache::stats()
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that had to run the closure
+    pub misses: u64,
+    /// Values written to the cache
+    pub insertions: u64,
+    /// Entries removed by tag invalidation
+    pub invalidations: u64,
+    /// Entries removed because their ttl elapsed
+    pub expirations: u64,
+    /// Entries removed to respect max_entries, max_bytes or max_weight
+    pub evictions: u64,
+    /// Serialized size of the keys and values currently stored
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct StatCounters {
+    pub(crate) hits: Counter,
+    pub(crate) misses: Counter,
+    pub(crate) insertions: Counter,
+    pub(crate) invalidations: Counter,
+    pub(crate) expirations: Counter,
+    pub(crate) evictions: Counter,
+    pub(crate) bytes: AtomicU64,
+    /// Sum of the entry weights, see DashmapCache::total_weight()
+    pub(crate) weight: AtomicU64,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    pub(crate) name: CacheName,
+}
+
+impl Default for StatCounters {
+    fn default() -> Self {
+        Self::named(default_name())
+    }
+}
+
+impl StatCounters {
+    /// Counters reported under the name label with the metrics feature
+    pub(crate) fn named(name: CacheName) -> Self {
+        Self {
+            hits: Counter::new("dashmap_cache_hits_total", &name, 0),
+            misses: Counter::new("dashmap_cache_misses_total", &name, 0),
+            insertions: Counter::new("dashmap_cache_insertions_total", &name, 0),
+            invalidations: Counter::new("dashmap_cache_invalidations_total", &name, 0),
+            expirations: Counter::new("dashmap_cache_expirations_total", &name, 0),
+            evictions: Counter::new("dashmap_cache_evictions_total", &name, 0),
+            bytes: AtomicU64::new(0),
+            weight: AtomicU64::new(0),
+            name,
+        }
+    }
+
+    pub(crate) fn incr(counter: &Counter, by: u64) {
+        counter.incr(by);
+    }
+
+    /// Accounts for the bytes and the weight of entry, stored under key
+    pub(crate) fn add_entry(&self, key: &[u8], entry: &Entry) {
+        let size = entry.size(key);
+        self.bytes.fetch_add(size as u64, Ordering::Relaxed);
+        self.weight.fetch_add(entry.weight, Ordering::Relaxed);
+        record_contents(&self.name, 1.0, size as f64);
+    }
+
+    pub(crate) fn sub_entry(&self, key: &[u8], entry: &Entry) {
+        let size = entry.size(key);
+        self.bytes.fetch_sub(size as u64, Ordering::Relaxed);
+        self.weight.fetch_sub(entry.weight, Ordering::Relaxed);
+        record_contents(&self.name, -1.0, -(size as f64));
+    }
+
+    /// Accounts for the removal of every entry
+    pub(crate) fn clear_contents(&self) {
+        self.bytes.store(0, Ordering::Relaxed);
+        self.weight.store(0, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        crate::instrument::clear_contents(&self.name);
+    }
+
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Clone for StatCounters {
+    fn clone(&self) -> Self {
+        let name = &self.name;
+        Self {
+            hits: self.hits.copy_for(name),
+            misses: self.misses.copy_for(name),
+            insertions: self.insertions.copy_for(name),
+            invalidations: self.invalidations.copy_for(name),
+            expirations: self.expirations.copy_for(name),
+            evictions: self.evictions.copy_for(name),
+            bytes: AtomicU64::new(self.bytes.load(Ordering::Relaxed)),
+            weight: AtomicU64::new(self.weight.load(Ordering::Relaxed)),
+            name: name.clone(),
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Serialized size of the cached keys and values, leaving out the maps and tag index overhead
+    pub fn approx_bytes(&self) -> u64 {
+        self.stats.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Sum of the weights of the cached entries, see cached_weighted()
+    pub fn total_weight(&self) -> u64 {
+        self.stats.weight.load(Ordering::Relaxed)
+    }
+
+    /// Zeroes every counter but bytes, which describes the current contents
+    pub fn reset_stats(&self) {
+        for counter in [
+            &self.stats.hits,
+            &self.stats.misses,
+            &self.stats.insertions,
+            &self.stats.invalidations,
+            &self.stats.expirations,
+            &self.stats.evictions,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use bytes::Bytes;
+use core::pin::Pin;
+use core::slice;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::time::Instant;
+
+use crate::entry::{Entry, Expiry};
+use crate::lock::KeyGuard;
+use crate::stats::StatCounters;
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Value cached once the source of a streamed value ended, each chunk being cached as it arrives
+/// Both live under keys of their own derived from the key of the arg, see DashmapCache::part_key(),
+/// so a value cached for the same arg by the other methods is never taken for a stream
+#[derive(Serialize, Deserialize)]
+struct StreamManifest {
+    chunks: u64,
+}
+
+/// Chunks of a value cached by cached_stream(), replayed from the cache or passed through from the source
+pub struct CachedStream<'a, St, S: Serializer> {
+    state: StreamState<'a, St, S>,
+    interrupted: bool,
+}
+
+enum StreamState<'a, St, S: Serializer> {
+    Replay(Replay<'a, S>),
+    Live(Box<LiveStream<'a, St, S>>),
+    Done,
+}
+
+/// Cached chunks read one at a time as they are polled
+struct Replay<'a, S: Serializer> {
+    cache: &'a DashmapCache<S>,
+    key: Vec<u8>,
+    next: u64,
+    chunks: u64,
+}
+
+/// Source stream being recorded, its key staying locked until it ends or is dropped
+struct LiveStream<'a, St, S: Serializer> {
+    source: Pin<Box<St>>,
+    /// Chunks cached so far, None once one of them could not be
+    chunks: Option<u64>,
+    finished: bool,
+    cache: &'a DashmapCache<S>,
+    tags: Vec<String>,
+    key: Vec<u8>,
+    expiry: Expiry,
+    started: Instant,
+    epochs: Vec<u64>,
+    _flight: KeyGuard<'a, S>,
+}
+
+impl<St, S: Serializer> LiveStream<'_, St, S> {
+    /// Caches chunk next to those already recorded
+    fn record(&mut self, chunk: &[u8]) {
+        let Some(index) = self.chunks else {
+            return;
+        };
+        match self.cache.encode(serde_bytes::Bytes::new(chunk)) {
+            Ok(val_bytes) => {
+                let key = self.cache.chunk_key(&self.key, index);
+                let entry = Entry::new(val_bytes, self.expiry);
+                self.cache.store(&self.tags, &key, entry, &self.epochs);
+                self.chunks = Some(index + 1);
+            }
+            Err(err) => {
+                self.cache.record_error(err);
+                self.forget();
+            }
+        }
+    }
+
+    /// Caches the manifest of the recorded chunks, once the source has ended
+    /// It expires along with the first chunk, the others being cached after it
+    fn finish(&mut self) {
+        self.finished = true;
+        let Some(chunks) = self.chunks else {
+            return;
+        };
+        let expiry = Expiry {
+            ttl: self
+                .expiry
+                .ttl
+                .map(|ttl| ttl.saturating_sub(self.started.elapsed())),
+            ..self.expiry
+        };
+        match self.cache.encode(&StreamManifest { chunks }) {
+            Ok(val_bytes) => {
+                let entry = Entry::new(val_bytes, expiry);
+                let key = self.cache.manifest_key(&self.key);
+                self.cache.store(&self.tags, &key, entry, &self.epochs);
+            }
+            Err(err) => {
+                self.cache.record_error(err);
+                self.forget();
+            }
+        }
+    }
+
+    /// Removes the chunks recorded so far, which no manifest will point to
+    fn forget(&mut self) {
+        for index in 0..self.chunks.take().unwrap_or(0) {
+            self.cache
+                .remove_key(&self.cache.chunk_key(&self.key, index));
+        }
+    }
+}
+
+impl<St, S: Serializer> Drop for LiveStream<'_, St, S> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.forget();
+        }
+    }
+}
+
+impl<St, S: Serializer> CachedStream<'_, St, S> {
+    /// Whether a replay ended early because one of its chunks was evicted or invalidated after it
+    /// started, the value being computed again by the next cached_stream() call
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+}
+
+impl<St, S> Stream for CachedStream<'_, St, S>
+where
+    St: Stream<Item = Bytes>,
+    S: Serializer,
+{
+    type Item = Bytes;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        let this = self.get_mut();
+        let live = match &mut this.state {
+            StreamState::Replay(replay) if replay.next == replay.chunks => {
+                this.state = StreamState::Done;
+                return Poll::Ready(None);
+            }
+            StreamState::Replay(replay) => {
+                let cache = replay.cache;
+                let chunk = cache.cached_chunk(&cache.chunk_key(&replay.key, replay.next));
+                if chunk.is_some() {
+                    replay.next += 1;
+                } else {
+                    cache.remove_key(&cache.manifest_key(&replay.key));
+                    this.interrupted = true;
+                    this.state = StreamState::Done;
+                }
+                return Poll::Ready(chunk);
+            }
+            StreamState::Live(live) => live,
+            StreamState::Done => return Poll::Ready(None),
+        };
+        match live.source.as_mut().poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(chunk)) => {
+                live.record(&chunk);
+                Poll::Ready(Some(chunk))
+            }
+            Poll::Ready(None) => {
+                live.finish();
+                this.state = StreamState::Done;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Caches a value produced as a stream of chunks, returning them as they arrive on a miss and
+    /// replaying them from the cache on a hit
+    /// Each chunk is cached as it passes through, under a key of its own carrying the same tags,
+    /// and the value is complete once the source stream ends; if it is dropped before, the chunks
+    /// cached so far are removed. Until then the key stays locked, concurrent calls for it
+    /// waiting to replay the cached chunks
+    /// Replays read the chunks one at a time as they are polled, neither side holding the whole value
+    /// A value one of whose chunks got evicted or expired is computed again, see CachedStream::is_interrupted()
+    /// for a chunk going away during a replay
+    pub async fn cached_stream<F, St, A>(
+        &self,
+        invalidate_keys: &[String],
+        closure: F,
+        arg: A,
+    ) -> Result<CachedStream<'_, St, S>, CacheError>
+    where
+        F: FnOnce(A) -> St,
+        St: Stream<Item = Bytes>,
+        A: Serialize,
+    {
+        let key = self.key_of(&arg)?;
+        if let Some(replay) = self.replay(&key)? {
+            return Ok(replay);
+        }
+        let flight = self.lock_key_at_async(key.clone()).await;
+        if let Some(replay) = self.replay(&key)? {
+            return Ok(replay);
+        }
+        if let Some(err) = self.cached_panic(&key) {
+            return Err(err);
+        }
+        StatCounters::incr(&self.stats.misses, 1);
+        let epochs = self.epochs_of(invalidate_keys);
+        let source = self.guard_panic(slice::from_ref(&key), || closure(arg))?;
+        Ok(CachedStream {
+            state: StreamState::Live(Box::new(LiveStream {
+                source: Box::pin(source),
+                chunks: Some(0),
+                finished: false,
+                cache: self,
+                tags: invalidate_keys.to_vec(),
+                key,
+                expiry: self.default_expiry(),
+                started: Instant::now(),
+                epochs,
+                _flight: flight,
+            })),
+            interrupted: false,
+        })
+    }
+
+    /// Key a part of the value streamed for key is cached under
+    /// Made of an encoded pair followed by key and never a single encoded arg, it can't be the key of one
+    fn part_key(&self, key: &[u8], part: &str, index: u64) -> Vec<u8> {
+        let mut part_key = rmp_serde::to_vec(&(part, index)).expect("a u64 always encodes");
+        part_key.extend_from_slice(key);
+        self.digest_key(part_key)
+    }
+
+    fn chunk_key(&self, key: &[u8], index: u64) -> Vec<u8> {
+        self.part_key(key, "chunk", index)
+    }
+
+    fn manifest_key(&self, key: &[u8]) -> Vec<u8> {
+        self.part_key(key, "manifest", 0)
+    }
+
+    /// Replay of the value cached for key, None unless its manifest and all of its chunks are cached
+    fn replay<St>(&self, key: &[u8]) -> Result<Option<CachedStream<'_, St, S>>, CacheError> {
+        let manifest_key = self.manifest_key(key);
+        let Some(StreamManifest { chunks }) = self.lookup(&manifest_key)? else {
+            return Ok(None);
+        };
+        let complete = (0..chunks).all(|index| {
+            let chunk_key = self.chunk_key(key, index);
+            self.contains_key(&chunk_key) || self.fetch_backend(&chunk_key).is_some()
+        });
+        if !complete {
+            self.remove_key(&manifest_key);
+            return Ok(None);
+        }
+        Ok(Some(CachedStream {
+            state: StreamState::Replay(Replay {
+                cache: self,
+                key: key.to_vec(),
+                next: 0,
+                chunks,
+            }),
+            interrupted: false,
+        }))
+    }
+
+    fn cached_chunk(&self, chunk_key: &[u8]) -> Option<Bytes> {
+        let stored = match self.live_entry(chunk_key) {
+            Some(entry) => entry.value.clone(),
+            None => self.fetch_backend(chunk_key)?,
+        };
+        match self.decode::<ByteBuf>(&stored) {
+            Ok(chunk) => Some(Bytes::from(chunk.into_vec())),
+            Err(err) => {
+                self.record_error(err);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::future::poll_fn;
+
+    struct Chunks(VecDeque<Bytes>);
+
+    impl Stream for Chunks {
+        type Item = Bytes;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+            Poll::Ready(self.get_mut().0.pop_front())
+        }
+    }
+
+    fn chunks(parts: &[&'static str]) -> Chunks {
+        Chunks(
+            parts
+                .iter()
+                .map(|part| Bytes::from_static(part.as_bytes()))
+                .collect(),
+        )
+    }
+
+    async fn collect<St: Stream<Item = Bytes>, S: Serializer>(
+        mut stream: CachedStream<'_, St, S>,
+    ) -> (Vec<Bytes>, bool) {
+        let mut collected = Vec::new();
+        while let Some(chunk) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            collected.push(chunk);
+        }
+        (collected, stream.is_interrupted())
+    }
+
+    #[tokio::test]
+    async fn chunks_are_cached_one_by_one_and_replayed() {
+        let cache = DashmapCache::new();
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["a", "b", "c"]), 1)
+            .await
+            .unwrap();
+        let (first, _) = collect(stream).await;
+        assert_eq!(cache.len(), 4);
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["other"]), 1)
+            .await
+            .unwrap();
+        assert_eq!(collect(stream).await, (first, false));
+    }
+
+    #[tokio::test]
+    async fn missing_chunks_get_the_value_computed_again() {
+        let cache = DashmapCache::new();
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["a", "b"]), 1)
+            .await
+            .unwrap();
+        collect(stream).await;
+        let key = cache.key_of(&1u32).unwrap();
+        let mut stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["c"]), 1)
+            .await
+            .unwrap();
+        cache.remove_key(&cache.chunk_key(&key, 1));
+        let first = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        assert_eq!(first, Some(Bytes::from_static(b"a")));
+        let (rest, interrupted) = collect(stream).await;
+        assert!(rest.is_empty() && interrupted);
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["c"]), 1)
+            .await
+            .unwrap();
+        assert_eq!(collect(stream).await.0, vec![Bytes::from_static(b"c")]);
+    }
+
+    #[tokio::test]
+    async fn values_cached_for_the_same_arg_are_left_alone() {
+        let cache = DashmapCache::new();
+        cache.put(&vec![], &7u32, &(3u64,)).unwrap();
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["a"]), 7)
+            .await
+            .unwrap();
+        assert_eq!(collect(stream).await.0, vec![Bytes::from_static(b"a")]);
+        assert_eq!(cache.get::<_, (u64,)>(&7u32).unwrap(), Some((3,)));
+        let stream = cache
+            .cached_stream(&[], |_x: u32| chunks(&["b"]), 7)
+            .await
+            .unwrap();
+        assert_eq!(
+            collect(stream).await,
+            (vec![Bytes::from_static(b"a")], false)
+        );
+        assert_eq!(cache.get::<_, (u64,)>(&7u32).unwrap(), Some((3,)));
+    }
+}
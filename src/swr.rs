@@ -0,0 +1,154 @@
+use core::hash::Hash;
+use dashmap::DashSet;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancel::AbortOnDrop;
+use crate::entry::{Entry, Expiry};
+use crate::{CacheError, DashmapCache, Serializer};
+
+/// Keys whose stale value is being recomputed in the background
+pub(crate) type Revalidating = DashSet<Vec<u8>>;
+
+/// Takes its key out of the revalidating set when the background task ends, aborted or not
+struct RevalidatingKey<S: Serializer> {
+    cache: Arc<DashmapCache<S>>,
+    key: Vec<u8>,
+}
+
+impl<S: Serializer> Drop for RevalidatingKey<S> {
+    fn drop(&mut self) {
+        self.cache.revalidating.remove(&self.key);
+    }
+}
+
+impl<S: Serializer + 'static> DashmapCache<S> {
+    /// Stale-while-revalidate version of tokio_cached()
+    /// Values are fresh for ttl, then served for stale_ttl more while the closure recomputes them in a
+    /// background task, at most one per key. Past both the entry is gone and the call waits like a miss
+    /// The background task writes under the key lock, after any fill of the key in progress
+    /// Failed revalidations leave the stale value in place, their error being kept for take_last_error()
+    pub async fn cached_swr<F, A, V>(
+        self: &Arc<Self>,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        stale_ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<V>,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b> + 'static,
+    {
+        let arg_bytes = self.key_of(&arg)?;
+        let expiry = Expiry {
+            ttl: Some(ttl),
+            stale_ttl,
+        };
+        match self.lookup_entry::<V>(&arg_bytes)? {
+            Some((val, true)) => {
+                if self.revalidating.insert(arg_bytes.clone()) {
+                    let epochs = self.epochs_of(invalidate_keys);
+                    let handle = AbortOnDrop(closure(&arg));
+                    let revalidating = RevalidatingKey {
+                        cache: self.clone(),
+                        key: arg_bytes,
+                    };
+                    let tags = invalidate_keys.clone();
+                    tokio::spawn(async move {
+                        let RevalidatingKey { cache, key } = &revalidating;
+                        let revalidated = cache
+                            .timed(async { Ok::<_, CacheError>(handle.await?) })
+                            .await
+                            .and_then(|val| cache.encode(&val));
+                        match revalidated {
+                            Ok(val_bytes) => {
+                                let _flight = cache.lock_key_at_async(key.clone()).await;
+                                cache.store(&tags, key, Entry::new(val_bytes, expiry), &epochs);
+                            }
+                            Err(err) => cache.record_error(err),
+                        }
+                    });
+                }
+                Ok(val)
+            }
+            Some((val, false)) => Ok(val),
+            None => {
+                self.tokio_cached_at(arg_bytes, invalidate_keys, expiry, closure, arg)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value whose serialization fails when it is 0
+    #[derive(Clone, Debug, PartialEq, Deserialize)]
+    struct Picky(u64);
+
+    impl Serialize for Picky {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if self.0 == 0 {
+                return Err(serde::ser::Error::custom("refusing to encode 0"));
+            }
+            serializer.serialize_u64(self.0)
+        }
+    }
+
+    async fn settle(cache: &DashmapCache) {
+        for _ in 0..20 {
+            if cache.revalidating.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_revalidations_keep_the_stale_value() {
+        let cache = Arc::new(DashmapCache::new());
+        let ttl = Duration::from_millis(10);
+        let stale_ttl = Duration::from_secs(60);
+        let computing = |val: u64| move |_: &u64| tokio::spawn(std::future::ready(Picky(val)));
+        let computed = cache
+            .cached_swr(&vec![], ttl, stale_ttl, computing(2), 1u64)
+            .await;
+        assert_eq!(computed.unwrap(), Picky(2));
+        tokio::time::sleep(ttl * 2).await;
+        let stale = cache
+            .cached_swr(&vec![], ttl, stale_ttl, computing(0), 1u64)
+            .await;
+        assert_eq!(stale.unwrap(), Picky(2));
+        settle(&cache).await;
+        assert!(matches!(
+            cache.take_last_error(),
+            Some(CacheError::Encode(_))
+        ));
+        let aborted = |_: &u64| {
+            let handle = tokio::spawn(std::future::pending::<Picky>());
+            handle.abort();
+            handle
+        };
+        let stale = cache
+            .cached_swr(&vec![], ttl, stale_ttl, aborted, 1u64)
+            .await;
+        assert_eq!(stale.unwrap(), Picky(2));
+        settle(&cache).await;
+        assert!(matches!(cache.take_last_error(), Some(CacheError::Join(_))));
+        let stale = cache
+            .cached_swr(&vec![], ttl, stale_ttl, computing(3), 1u64)
+            .await;
+        assert_eq!(stale.unwrap(), Picky(2));
+        settle(&cache).await;
+        let fresh = cache
+            .cached_swr(&vec![], ttl, stale_ttl, computing(0), 1u64)
+            .await;
+        assert_eq!(fresh.unwrap(), Picky(3));
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::entry::Entry;
+use crate::{DashmapCache, Serializer};
+
+/// Limits applying to the entries carrying a tag, on top of the cache wide ones
+/// An entry with several tags gets the strictest ttl among them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagPolicy {
+    /// Longest time an entry with the tag is kept, whatever ttl it was cached with
+    #[serde(with = "crate::duration::option")]
+    pub ttl: Option<Duration>,
+    /// Most entries the tag can have, the ones to drop being chosen by the eviction policy
+    pub max_entries: Option<usize>,
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Applies policy to the entries stored with tag from now on, replacing its previous policy
+    pub fn set_tag_policy(&self, tag: &str, policy: TagPolicy) {
+        self.tag_policies.insert(tag.to_owned(), policy);
+    }
+
+    /// Returns the policy tag had, entries already stored keep the ttl it gave them
+    pub fn remove_tag_policy(&self, tag: &str) -> Option<TagPolicy> {
+        self.tag_policies.remove(tag).map(|(_tag, policy)| policy)
+    }
+
+    pub fn tag_policy(&self, tag: &str) -> Option<TagPolicy> {
+        self.tag_policies.get(tag).map(|policy| *policy)
+    }
+
+    /// Shortens the expiry of entry to the ttl of its tag policies
+    pub(crate) fn cap_tag_ttl(&self, entry: &mut Entry) {
+        if self.tag_policies.is_empty() {
+            return;
+        }
+        let Some(ttl) = entry
+            .tags
+            .iter()
+            .filter_map(|tag| self.tag_policies.get(tag)?.ttl)
+            .min()
+        else {
+            return;
+        };
+        let deadline = Instant::now() + ttl;
+        entry.expires_at = Some(entry.expires_at.map_or(deadline, |at| at.min(deadline)));
+        entry.stale_at = entry.stale_at.map(|at| at.min(deadline));
+    }
+
+    /// Brings each of tags back under the max_entries of its policy, with the same slack and keeping
+    /// the key just written as evict_if_needed()
+    pub(crate) fn evict_tags_if_needed(&self, tags: &[String], written: Option<&[u8]>) {
+        if self.tag_policies.is_empty() {
+            return;
+        }
+        for tag in tags {
+            let Some(max) = self
+                .tag_policies
+                .get(tag)
+                .and_then(|policy| policy.max_entries)
+            else {
+                continue;
+            };
+            let keys: Vec<Vec<u8>> = match self.tags.get(tag) {
+                Some(keys) if keys.len() > max => keys.iter().map(|key| key.clone()).collect(),
+                _ => continue,
+            };
+            let excess = keys.len().saturating_sub(max - max / 16);
+            let mut scored: Vec<((u64, u64), Vec<u8>)> = keys
+                .into_iter()
+                .filter(|key| written != Some(key.as_slice()))
+                .filter_map(|key| {
+                    let score = self.eviction_score(&*self.inner.get(&key)?);
+                    Some((score, key))
+                })
+                .collect();
+            scored.sort_unstable_by_key(|(score, _key)| *score);
+            for (_score, key) in scored.into_iter().take(excess) {
+                self.evict_key(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn tag_ttls_cap_the_ttl_of_their_entries() {
+        let cache = DashmapCache::new();
+        let short = Duration::from_secs(10);
+        let long = Duration::from_secs(100);
+        cache.set_tag_policy(
+            "short",
+            TagPolicy {
+                ttl: Some(short),
+                max_entries: None,
+            },
+        );
+        cache.set_tag_policy(
+            "long",
+            TagPolicy {
+                ttl: Some(long),
+                max_entries: None,
+            },
+        );
+        let hour = Duration::from_secs(3600);
+        cache.put(&tags(&["short"]), &1u8, &1u8).unwrap();
+        cache
+            .cached_with_ttl(&tags(&["long"]), hour, |x| *x, 2u8)
+            .unwrap();
+        cache
+            .cached_with_ttl(&tags(&["long", "short"]), hour, |x| *x, 3u8)
+            .unwrap();
+        cache
+            .cached_with_ttl(&tags(&["long"]), Duration::from_secs(1), |x| *x, 4u8)
+            .unwrap();
+        cache.put(&tags(&["other"]), &6u8, &6u8).unwrap();
+        let now = Instant::now();
+        let left = |arg: u8| {
+            let entry = cache.inner.get(&cache.key_of(&arg).unwrap()).unwrap();
+            entry.expires_at.map(|at| at - now)
+        };
+        let within = |left: Option<Duration>, ttl: Duration| {
+            left.is_some_and(|left| left <= ttl && left > ttl / 2)
+        };
+        assert!(within(left(1), short));
+        assert!(within(left(2), long));
+        assert!(within(left(3), short));
+        assert!(within(left(4), Duration::from_secs(1)));
+        assert_eq!(left(6), None);
+        assert_eq!(
+            cache
+                .remove_tag_policy("short")
+                .and_then(|policy| policy.ttl),
+            Some(short)
+        );
+        cache.put(&tags(&["short"]), &7u8, &7u8).unwrap();
+        assert_eq!(left(7), None);
+    }
+
+    #[test]
+    fn tags_keep_at_most_their_max_entries() {
+        let cache = DashmapCache::new();
+        cache.set_tag_policy(
+            "capped",
+            TagPolicy {
+                ttl: None,
+                max_entries: Some(4),
+            },
+        );
+        for i in 0..10u32 {
+            cache.put(&tags(&["capped"]), &i, &i).unwrap();
+            cache.put(&tags(&["free"]), &(100 + i), &i).unwrap();
+        }
+        let tag_len = |tag: &str| cache.tags.get(tag).map_or(0, |keys| keys.len());
+        assert_eq!(tag_len("capped"), 4);
+        assert_eq!(tag_len("free"), 10);
+        assert_eq!(cache.stats().evictions, 6);
+        for i in 0..10u32 {
+            assert_eq!(cache.contains(&i).unwrap(), i >= 6, "{i}");
+        }
+        // Reading an entry makes it the most recently used, the least recent one going instead
+        assert_eq!(cache.get::<_, u32>(&6u32).unwrap(), Some(6));
+        cache.put(&tags(&["capped"]), &10u32, &10u32).unwrap();
+        assert!(cache.contains(&6u32).unwrap());
+        assert!(!cache.contains(&7u32).unwrap());
+    }
+}
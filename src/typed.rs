@@ -0,0 +1,238 @@
+use core::future::Future;
+use core::hash::Hash;
+use core::marker::PhantomData;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::TypeId;
+use std::marker::{Send, Sync};
+use std::time::Duration;
+
+use crate::entry::Expiry;
+use crate::key::decode_key;
+use crate::{CacheError, DashmapCache, MsgPack, Serializer};
+
+/// Handle on a DashmapCache restricted to one argument type and one return type
+/// Every key is prefixed with its namespace, so handles on different namespaces never share entries
+#[derive(Debug)]
+pub struct TypedCache<'a, A, V, S: Serializer = MsgPack> {
+    cache: &'a DashmapCache<S>,
+    prefix: Vec<u8>,
+    _types: PhantomData<fn(A) -> V>,
+}
+
+impl<A, V, S: Serializer> Clone for TypedCache<'_, A, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache,
+            prefix: self.prefix.clone(),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Binds namespace to the (A, V) type pair and returns a handle keyed under it
+    /// Registering the same namespace again with another type pair fails with CacheError::NamespaceConflict
+    pub fn register_type<A, V>(
+        &self,
+        namespace: &str,
+    ) -> Result<TypedCache<'_, A, V, S>, CacheError>
+    where
+        A: 'static,
+        V: 'static,
+    {
+        let types = (TypeId::of::<A>(), TypeId::of::<V>());
+        let registered = *self.namespaces.entry(namespace.to_owned()).or_insert(types);
+        if registered != types {
+            return Err(CacheError::NamespaceConflict(namespace.to_owned()));
+        }
+        Ok(self.typed(namespace))
+    }
+
+    /// Handle keyed under namespace, without binding it to the (A, V) type pair
+    /// Use register_type() to have reuse of the namespace with other types rejected
+    pub fn typed<A, V>(&self, namespace: &str) -> TypedCache<'_, A, V, S> {
+        TypedCache {
+            cache: self,
+            prefix: rmp_serde::to_vec(namespace).expect("a str always encodes"),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<A, V, S: Serializer> TypedCache<'_, A, V, S>
+where
+    A: Hash + Sync + Send + Eq + Serialize,
+    V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+{
+    fn key(&self, arg: &A) -> Result<Vec<u8>, CacheError> {
+        let mut key = self.prefix.clone();
+        rmp_serde::encode::write(&mut key, arg)?;
+        Ok(self.cache.digest_key(key))
+    }
+
+    /// Namespaced version of DashmapCache::remove()
+    pub fn remove(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.cache.remove_key(&self.key(arg)?))
+    }
+
+    /// Namespaced version of DashmapCache::contains()
+    pub fn contains(&self, arg: &A) -> Result<bool, CacheError> {
+        Ok(self.cache.contains_key(&self.key(arg)?))
+    }
+
+    /// Namespaced version of DashmapCache::get()
+    pub fn get(&self, arg: &A) -> Result<Option<V>, CacheError> {
+        self.cache.get_key(&self.key(arg)?)
+    }
+
+    /// Namespaced version of DashmapCache::put()
+    pub fn put(&self, invalidate_keys: &Vec<String>, arg: &A, value: &V) -> Result<(), CacheError> {
+        self.cache.put_key(invalidate_keys, &self.key(arg)?, value)
+    }
+
+    /// Namespaced version of DashmapCache::refresh_cache()
+    pub fn refresh_cache<F>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+    {
+        self.cache
+            .refresh_cache_at(self.key(&arg)?, invalidate_keys, closure, arg)
+    }
+
+    /// Namespaced version of DashmapCache::cached()
+    pub fn cached<F>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+    {
+        self.cache.cached_at(
+            self.key(&arg)?,
+            invalidate_keys,
+            self.cache.default_expiry(),
+            closure,
+            arg,
+        )
+    }
+
+    /// Namespaced version of DashmapCache::cached_with_ttl()
+    pub fn cached_with_ttl<F>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        ttl: Duration,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> V,
+    {
+        self.cache.cached_at(
+            self.key(&arg)?,
+            invalidate_keys,
+            Expiry::after(ttl),
+            closure,
+            arg,
+        )
+    }
+
+    /// Namespaced version of DashmapCache::async_cached()
+    pub async fn async_cached<F, Fut>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: FnOnce(A) -> Fut,
+        Fut: Future<Output = V>,
+    {
+        self.cache
+            .async_cached_at(
+                self.key(&arg)?,
+                invalidate_keys,
+                self.cache.default_expiry(),
+                closure,
+                arg,
+            )
+            .await
+    }
+
+    /// Namespaced version of DashmapCache::tokio_cached()
+    #[cfg(feature = "tokio")]
+    pub async fn tokio_cached<F>(
+        &self,
+        invalidate_keys: &Vec<String>,
+        closure: F,
+        arg: A,
+    ) -> Result<V, CacheError>
+    where
+        F: Fn(&A) -> tokio::task::JoinHandle<V>,
+    {
+        self.cache
+            .tokio_cached_at(
+                self.key(&arg)?,
+                invalidate_keys,
+                self.cache.default_expiry(),
+                closure,
+                arg,
+            )
+            .await
+    }
+}
+
+impl<A: DeserializeOwned, V, S: Serializer> TypedCache<'_, A, V, S> {
+    /// Args of the live entries of the namespace, None under a digest KeyStrategy as for
+    /// DashmapCache::keys_as()
+    pub fn keys(&self) -> Option<Vec<A>> {
+        if !self.cache.keys_decodable() {
+            return None;
+        }
+        Some(
+            self.cache
+                .keys()
+                .iter()
+                .filter_map(|key| decode_key(key.strip_prefix(self.prefix.as_slice())?))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_types_never_share_entries() {
+        let cache = DashmapCache::new();
+        let squares = cache.register_type::<u32, u64>("squares").unwrap();
+        let names = cache.register_type::<u32, String>("names").unwrap();
+        assert_eq!(squares.cached(&vec![], |x| u64::from(x * x), 3).unwrap(), 9);
+        assert_eq!(names.cached(&vec![], |x| format!("#{x}"), 3).unwrap(), "#3");
+        assert_eq!(squares.cached(&vec![], |_| 0, 3).unwrap(), 9);
+        assert_eq!(names.cached(&vec![], |_| String::new(), 3).unwrap(), "#3");
+    }
+
+    #[test]
+    fn reusing_a_namespace_with_other_types_is_rejected() {
+        let cache = DashmapCache::new();
+        cache.register_type::<u32, u64>("squares").unwrap();
+        assert!(cache.register_type::<u32, u64>("squares").is_ok());
+        assert!(matches!(
+            cache.register_type::<u32, String>("squares"),
+            Err(CacheError::NamespaceConflict(namespace)) if namespace == "squares"
+        ));
+        assert!(matches!(
+            cache.register_type::<String, u64>("squares"),
+            Err(CacheError::NamespaceConflict(_))
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::sync::atomic::Ordering;
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Counter bumped each time tag is invalidated, and by clear()
+    /// Values tagged with tag read while it stays the same were not invalidated since
+    /// The cache keeps an exact counter for each tag this is called for, invalidations of other tags
+    /// never moving it
+    pub fn epoch(&self, tag: &str) -> u64 {
+        self.apply_remote_invalidations();
+        let invalidations = self.tag_epochs.watch(tag);
+        self.generation.load(Ordering::Relaxed) + invalidations
+    }
+
+    /// Same as get(), along with the epoch of tag the value is current for
+    /// Comparing it with epoch() later tells whether the value was invalidated meanwhile, as long
+    /// as it is tagged with tag
+    pub fn get_versioned<A, V>(&self, tag: &str, arg: &A) -> Result<Option<(V, u64)>, CacheError>
+    where
+        A: Serialize,
+        V: for<'b> Deserialize<'b>,
+    {
+        // Taken before reading, an invalidation racing with the read makes the epoch look outdated
+        let epoch = self.epoch(tag);
+        Ok(self.get(arg)?.map(|val| (val, epoch)))
+    }
+
+    /// Same as cached(), as long as tag is still at epoch, the entry being tagged with it too
+    /// Returns None without computing anything once tag was invalidated past epoch, and None as well
+    /// when it gets invalidated while the value is computed
+    pub fn cached_if_epoch<F, A, V>(
+        &self,
+        invalidate_keys: &[String],
+        tag: &str,
+        epoch: u64,
+        closure: F,
+        arg: A,
+    ) -> Result<Option<V>, CacheError>
+    where
+        F: Fn(&A) -> V,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        if self.epoch(tag) != epoch {
+            return Ok(None);
+        }
+        let mut tags = invalidate_keys.to_vec();
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_owned());
+        }
+        let val = self.cached(&tags, closure, arg)?;
+        Ok((self.epoch(tag) == epoch).then_some(val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epochs_only_move_with_their_own_tag() {
+        let cache = DashmapCache::new();
+        let epoch = cache.epoch("a");
+        for i in 0..1000 {
+            cache.invalidate(&format!("other-{i}"));
+        }
+        assert_eq!(cache.epoch("a"), epoch);
+        let val = cache
+            .cached_if_epoch(&[], "a", epoch, |x: &u32| x * 2, 1)
+            .unwrap();
+        assert_eq!(val, Some(2));
+        cache.invalidate("a");
+        assert_eq!(cache.epoch("a"), epoch + 1);
+        assert_eq!(
+            cache
+                .cached_if_epoch(&[], "a", epoch, |x: &u32| x * 2, 1)
+                .unwrap(),
+            None
+        );
+        cache.clear();
+        assert_eq!(cache.epoch("a"), epoch + 2);
+    }
+}
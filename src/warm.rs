@@ -0,0 +1,94 @@
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+use std::sync::Mutex;
+
+use crate::{CacheError, DashmapCache, Serializer};
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Caches the value of each (tags, arg) pair through cached(), running up to concurrency
+    /// computations at once on scoped threads, and returns once all of them are done
+    /// Args already cached are skipped, and the key locks keep pairs sharing an arg from computing it twice
+    /// Stops starting new computations after the first error, which is returned
+    pub fn warm<I, F, A, V>(
+        &self,
+        entries: I,
+        closure: F,
+        concurrency: usize,
+    ) -> Result<(), CacheError>
+    where
+        I: IntoIterator<Item = (Vec<String>, A)>,
+        I::IntoIter: Send,
+        F: Fn(&A) -> V + Sync,
+        A: Hash + Sync + Send + Eq + Serialize,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    {
+        let entries = Mutex::new(entries.into_iter());
+        let failure: Mutex<Option<CacheError>> = Mutex::new(None);
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency.max(1) {
+                scope.spawn(|| loop {
+                    if failure.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+                        break;
+                    }
+                    let next = entries.lock().unwrap_or_else(|e| e.into_inner()).next();
+                    let Some((tags, arg)) = next else {
+                        break;
+                    };
+                    if let Err(err) = self.cached(&tags, &closure, arg) {
+                        failure
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .get_or_insert(err);
+                    }
+                });
+            }
+        });
+        match failure.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Tokio version of warm(), each computation being a tokio_cached() call in a task of its own
+    #[cfg(feature = "tokio")]
+    pub async fn tokio_warm<I, F, A, V>(
+        self: &std::sync::Arc<Self>,
+        entries: I,
+        closure: F,
+        concurrency: usize,
+    ) -> Result<(), CacheError>
+    where
+        S: 'static,
+        I: IntoIterator<Item = (Vec<String>, A)>,
+        F: Fn(&A) -> tokio::task::JoinHandle<V> + Send + Sync + 'static,
+        A: Hash + Sync + Send + Eq + Serialize + 'static,
+        V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b> + 'static,
+    {
+        let closure = std::sync::Arc::new(closure);
+        let mut tasks = tokio::task::JoinSet::new();
+        let mut result = Ok(());
+        for (tags, arg) in entries {
+            if tasks.len() >= concurrency.max(1) {
+                if let Some(done) = tasks.join_next().await {
+                    result = result.and(done.map_err(CacheError::from).and_then(|done| done));
+                }
+            }
+            if result.is_err() {
+                break;
+            }
+            let cache = self.clone();
+            let closure = closure.clone();
+            tasks.spawn(async move {
+                cache
+                    .tokio_cached(&tags, |arg: &A| closure(arg), arg)
+                    .await
+                    .map(drop)
+            });
+        }
+        while let Some(done) = tasks.join_next().await {
+            result = result.and(done.map_err(CacheError::from).and_then(|done| done));
+        }
+        result
+    }
+}
@@ -0,0 +1,110 @@
+use core::future::Future;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::marker::{Send, Sync};
+
+use crate::{CacheError, DashmapCache, MsgPack, Serializer, TypedCache};
+
+/// Function memoized in a DashmapCache along with its tags, obtained from DashmapCache::wrap()
+#[derive(Debug)]
+pub struct CachedFn<'a, A, V, F, S: Serializer = MsgPack> {
+    handle: TypedCache<'a, A, V, S>,
+    tags: Vec<String>,
+    func: F,
+}
+
+/// Async version of CachedFn, obtained from DashmapCache::wrap_async()
+#[derive(Debug)]
+pub struct AsyncCachedFn<'a, A, V, F, S: Serializer = MsgPack> {
+    handle: TypedCache<'a, A, V, S>,
+    tags: Vec<String>,
+    func: F,
+}
+
+impl<S: Serializer> DashmapCache<S> {
+    /// Memoizes func under namespace, every call being tagged with invalidate_keys
+    /// Keys are namespaced as with typed(), give each wrapped function its own namespace
+    pub fn wrap<A, V, F>(
+        &self,
+        namespace: &str,
+        invalidate_keys: Vec<String>,
+        func: F,
+    ) -> CachedFn<'_, A, V, F, S>
+    where
+        F: Fn(&A) -> V,
+    {
+        CachedFn {
+            handle: self.typed(namespace),
+            tags: invalidate_keys,
+            func,
+        }
+    }
+
+    /// Same as wrap() for a function returning a future, such as `|arg| async move { .. }`
+    pub fn wrap_async<A, V, F, Fut>(
+        &self,
+        namespace: &str,
+        invalidate_keys: Vec<String>,
+        func: F,
+    ) -> AsyncCachedFn<'_, A, V, F, S>
+    where
+        F: Fn(A) -> Fut,
+        Fut: Future<Output = V>,
+    {
+        AsyncCachedFn {
+            handle: self.typed(namespace),
+            tags: invalidate_keys,
+            func,
+        }
+    }
+}
+
+impl<A, V, F, S> CachedFn<'_, A, V, F, S>
+where
+    A: Hash + Sync + Send + Eq + Serialize,
+    V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    F: Fn(&A) -> V,
+    S: Serializer,
+{
+    /// Cached value of the function for arg, computed on a miss
+    pub fn call(&self, arg: A) -> Result<V, CacheError> {
+        self.handle.cached(&self.tags, &self.func, arg)
+    }
+
+    /// Computes the value for arg again and caches it, see DashmapCache::refresh_cache()
+    pub fn refresh(&self, arg: A) -> Result<V, CacheError> {
+        self.handle.refresh_cache(&self.tags, &self.func, arg)
+    }
+
+    /// Drops the value cached for arg
+    pub fn forget(&self, arg: &A) -> Result<bool, CacheError> {
+        self.handle.remove(arg)
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+impl<A, V, F, Fut, S> AsyncCachedFn<'_, A, V, F, S>
+where
+    A: Hash + Sync + Send + Eq + Serialize,
+    V: Send + Sync + Clone + Serialize + for<'b> Deserialize<'b>,
+    F: Fn(A) -> Fut,
+    Fut: Future<Output = V>,
+    S: Serializer,
+{
+    /// Cached value of the function for arg, awaited on a miss
+    pub async fn call(&self, arg: A) -> Result<V, CacheError> {
+        self.handle.async_cached(&self.tags, &self.func, arg).await
+    }
+
+    /// Drops the value cached for arg
+    pub fn forget(&self, arg: &A) -> Result<bool, CacheError> {
+        self.handle.remove(arg)
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "macros")]
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+
+use dashmap_cache::{dashmap_cached, DashmapCache};
+
+static CACHE: LazyLock<DashmapCache> = LazyLock::new(DashmapCache::new);
+
+static SQUARES: AtomicUsize = AtomicUsize::new(0);
+
+#[dashmap_cached(cache = CACHE, tags = ["squares"])]
+fn square(x: u64, mut offset: u64) -> u64 {
+    SQUARES.fetch_add(1, Ordering::SeqCst);
+    offset += x * x;
+    offset
+}
+
+#[test]
+fn sync_functions_are_memoized_and_tagged() {
+    assert_eq!(square(3, 1), 10);
+    assert_eq!(square(3, 1), 10);
+    assert_eq!(SQUARES.load(Ordering::SeqCst), 1);
+    assert_eq!(square(4, 0), 16);
+    assert_eq!(SQUARES.load(Ordering::SeqCst), 2);
+    CACHE.invalidate("squares");
+    assert_eq!(square(3, 1), 10);
+    assert_eq!(SQUARES.load(Ordering::SeqCst), 3);
+}
+
+static GREETINGS: AtomicUsize = AtomicUsize::new(0);
+
+#[dashmap_cached(cache = CACHE)]
+async fn greeting(name: String) -> String {
+    GREETINGS.fetch_add(1, Ordering::SeqCst);
+    format!("hello {name}")
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[tokio::test]
+async fn async_functions_are_memoized() {
+    let first = greeting("ann".to_owned());
+    assert_send(&first);
+    assert_eq!(first.await, "hello ann");
+    assert_eq!(greeting("ann".to_owned()).await, "hello ann");
+    assert_eq!(GREETINGS.load(Ordering::SeqCst), 1);
+}
+
+/// Value or arg whose serialization always fails
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+struct Unencodable(u32);
+
+impl Serialize for Unencodable {
+    fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("refusing to encode"))
+    }
+}
+
+static UNENCODABLE_VALUES: AtomicUsize = AtomicUsize::new(0);
+
+#[dashmap_cached(cache = CACHE)]
+fn unencodable_value(x: u32) -> Unencodable {
+    UNENCODABLE_VALUES.fetch_add(1, Ordering::SeqCst);
+    Unencodable(x)
+}
+
+static UNENCODABLE_ARGS: AtomicUsize = AtomicUsize::new(0);
+
+#[dashmap_cached(cache = CACHE)]
+async fn unencodable_arg(x: Unencodable) -> u32 {
+    UNENCODABLE_ARGS.fetch_add(1, Ordering::SeqCst);
+    x.0
+}
+
+#[tokio::test]
+async fn cache_errors_never_run_the_body_twice() {
+    assert_eq!(unencodable_value(1), Unencodable(1));
+    assert_eq!(UNENCODABLE_VALUES.load(Ordering::SeqCst), 1);
+    assert_eq!(unencodable_arg(Unencodable(2)).await, 2);
+    assert_eq!(UNENCODABLE_ARGS.load(Ordering::SeqCst), 1);
+}
+
+static PANIC_CACHE: LazyLock<DashmapCache> = LazyLock::new(|| {
+    DashmapCache::builder()
+        .panic_policy(dashmap_cache::PanicPolicy::Error)
+        .build()
+});
+
+static PANICS: AtomicUsize = AtomicUsize::new(0);
+
+#[dashmap_cached(cache = PANIC_CACHE)]
+fn panicking(_x: u32) -> u32 {
+    PANICS.fetch_add(1, Ordering::SeqCst);
+    panic!("no value")
+}
+
+#[test]
+fn caught_panics_resurface_without_running_the_body_again() {
+    let err = std::panic::catch_unwind(|| panicking(1)).unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(message.contains("no value"), "{message}");
+    assert_eq!(PANICS.load(Ordering::SeqCst), 1);
+}
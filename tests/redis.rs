@@ -0,0 +1,149 @@
+#![cfg(feature = "redis")]
+//! Tests against a live Redis server, run with
+//! `DASHMAP_CACHE_REDIS_URL=redis://127.0.0.1/ cargo test --features redis -- --ignored`
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dashmap_cache::{CacheBackend, DashmapCache, RedisBackend, RedisTransport};
+
+/// Backend on the server of DASHMAP_CACHE_REDIS_URL, under a prefix of its own so that tests
+/// running at once or left over from earlier runs don't see each other's keys
+fn backend(test: &str) -> RedisBackend {
+    RedisBackend::open(&url())
+        .unwrap()
+        .with_prefix(&format!("{}:", namespace(test)))
+}
+
+fn url() -> String {
+    std::env::var("DASHMAP_CACHE_REDIS_URL")
+        .expect("set DASHMAP_CACHE_REDIS_URL to the Redis server to run the tests against")
+}
+
+fn namespace(test: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("dashmap-cache-test:{test}:{nanos}")
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn inserts_are_read_back_with_their_tags() {
+    let redis = backend("insert");
+    assert_eq!(redis.get(b"a").unwrap(), None);
+    let tags = vec!["users".to_owned(), "admins".to_owned()];
+    redis.insert(b"a", b"ann", &tags, None).unwrap();
+    let stored = redis.get(b"a").unwrap().unwrap();
+    assert_eq!(stored.value, b"ann");
+    assert_eq!(stored.tags, tags);
+    assert_eq!(stored.ttl, None);
+    redis
+        .insert(b"b", b"bob", &[], Some(Duration::from_secs(60)))
+        .unwrap();
+    let ttl = redis.get(b"b").unwrap().unwrap().ttl.unwrap();
+    assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn removals_report_whether_there_was_a_value() {
+    let redis = backend("remove");
+    redis
+        .insert(b"a", b"ann", &["users".to_owned()], None)
+        .unwrap();
+    assert!(redis.remove(b"a").unwrap());
+    assert!(!redis.remove(b"a").unwrap());
+    assert_eq!(redis.get(b"a").unwrap(), None);
+    // The removed key is no longer in its tag set, a new value for it survives invalidations
+    // of the tags it had before
+    redis.insert(b"a", b"amy", &[], None).unwrap();
+    redis.invalidate_tag("users").unwrap();
+    assert_eq!(redis.get(b"a").unwrap().unwrap().value, b"amy");
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn values_expire_with_their_ttl() {
+    let redis = backend("expiry");
+    redis
+        .insert(
+            b"a",
+            b"ann",
+            &["users".to_owned()],
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+    redis
+        .insert(b"b", b"bob", &["users".to_owned()], None)
+        .unwrap();
+    assert!(redis.get(b"a").unwrap().is_some());
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(redis.get(b"a").unwrap(), None);
+    assert_eq!(redis.get(b"b").unwrap().unwrap().value, b"bob");
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn invalidating_a_tag_removes_its_keys_only() {
+    let redis = backend("invalidate");
+    redis
+        .insert(b"a", b"ann", &["users".to_owned()], None)
+        .unwrap();
+    redis
+        .insert(
+            b"b",
+            b"bob",
+            &["users".to_owned(), "admins".to_owned()],
+            None,
+        )
+        .unwrap();
+    redis
+        .insert(b"c", b"cat", &["pets".to_owned()], None)
+        .unwrap();
+    redis.invalidate_tag("users").unwrap();
+    assert_eq!(redis.get(b"a").unwrap(), None);
+    assert_eq!(redis.get(b"b").unwrap(), None);
+    assert_eq!(redis.get(b"c").unwrap().unwrap().value, b"cat");
+    // Invalidating a tag whose keys are already gone is a no-op
+    redis.invalidate_tag("admins").unwrap();
+    redis.invalidate_tag("nobody").unwrap();
+    assert_eq!(redis.get(b"c").unwrap().unwrap().value, b"cat");
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn caches_sharing_a_backend_see_each_other_s_values_and_invalidations() {
+    let redis = std::sync::Arc::new(backend("shared"));
+    let first = DashmapCache::builder().backend(redis.clone()).build();
+    let second = DashmapCache::builder().backend(redis.clone()).build();
+    let tags = vec!["squares".to_owned()];
+    assert_eq!(first.cached(&tags, |x| x * x, 3u64).unwrap(), 9);
+    assert_eq!(second.cached(&tags, |_| 0, 3u64).unwrap(), 9);
+    first.invalidate("squares");
+    let third = DashmapCache::builder().backend(redis).build();
+    assert_eq!(third.cached(&tags, |x| x + 1, 3u64).unwrap(), 4);
+}
+
+#[test]
+#[ignore = "needs a Redis server, set DASHMAP_CACHE_REDIS_URL"]
+fn clones_get_each_other_s_invalidations() {
+    let transport = RedisTransport::open(&url())
+        .unwrap()
+        .with_channel(&namespace("transport"));
+    let cache = DashmapCache::builder()
+        .invalidation_transport(transport)
+        .build();
+    let clone = cache.clone();
+    let tags = vec!["users".to_owned()];
+    cache.put(&tags, &1u8, &1u8).unwrap();
+    // Publishing again until delivered, the subscription thread may not be listening yet
+    for _ in 0..100 {
+        clone.invalidate("users");
+        if !cache.contains(&1u8).unwrap() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    panic!("the invalidation of the clone never reached the cache");
+}
@@ -0,0 +1,74 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use dashmap_cache::DashmapCache;
+
+/// Counts the allocations of the current thread, so that tests running alongside don't add up
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(run: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    run();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn arg(i: u32) -> (String, u32) {
+    (format!("key number {i}"), i)
+}
+
+fn value(arg: &(String, u32)) -> Vec<u64> {
+    vec![u64::from(arg.1); 64]
+}
+
+#[test]
+fn refresh_many_allocates_less_than_refresh_cache() {
+    const N: u32 = 200;
+    let tags = vec!["tag".to_owned()];
+    let one_by_one = DashmapCache::new();
+    let batched = DashmapCache::new();
+    let args: Vec<_> = (0..N).map(arg).collect();
+
+    let mut values = Vec::new();
+    let singles = allocations(|| {
+        for arg in args.clone() {
+            values.push(one_by_one.refresh_cache(&tags, value, arg).unwrap());
+        }
+    });
+    let mut batch = Vec::new();
+    let batch_allocations = allocations(|| {
+        batch = batched.refresh_many(&tags, value, args.clone()).unwrap();
+    });
+
+    assert_eq!(batch, values);
+    for arg in &args {
+        let cached: Vec<u64> = batched.cached(&tags, |_| Vec::new(), arg.clone()).unwrap();
+        assert_eq!(cached, value(arg));
+    }
+    assert!(
+        batch_allocations < singles,
+        "refresh_many made {batch_allocations} allocations, refresh_cache {singles}"
+    );
+}